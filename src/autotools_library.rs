@@ -0,0 +1,334 @@
+use crate::{
+    CompiledLibraryName, Library, LibraryCompilationContext, LibraryDependencies, LibraryLocation,
+    LibraryOptions, LibraryTarget,
+};
+use rustc_version::version_meta;
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use user_error::UserFacingError;
+
+use serde::{Deserialize, Serialize};
+
+/// A library that is built with the classic `./configure && make && make install` sequence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutotoolsLibrary {
+    name: String,
+    exported_name: Option<String>,
+    compiled_name: CompiledLibraryName,
+    source_location: LibraryLocation,
+    release_location: Option<LibraryLocation>,
+    configure_flags: Vec<String>,
+    dependencies: LibraryDependencies,
+    options: LibraryOptions,
+    env_vars: HashMap<OsString, OsString>,
+    header_directories: Vec<PathBuf>,
+}
+
+impl AutotoolsLibrary {
+    pub fn new(name: &str, location: LibraryLocation) -> Self {
+        Self {
+            name: name.to_owned(),
+            exported_name: None,
+            compiled_name: CompiledLibraryName::Default,
+            source_location: location,
+            release_location: None,
+            configure_flags: vec![],
+            dependencies: LibraryDependencies::new(),
+            options: Default::default(),
+            env_vars: Default::default(),
+            header_directories: vec![Path::new("include").to_path_buf()],
+        }
+    }
+
+    /// Pass an extra flag to the `configure` script, for example `--without-python`
+    pub fn configure_flag(mut self, flag: impl Into<String>) -> Self {
+        self.configure_flags.push(flag.into());
+        self
+    }
+
+    pub fn depends(mut self, library: Box<dyn Library>) -> Self {
+        self.dependencies = self.dependencies.push(library);
+        self
+    }
+
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.env_vars.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn compiled_name(mut self, compiled_name: CompiledLibraryName) -> Self {
+        self.compiled_name = compiled_name;
+        self
+    }
+
+    pub fn with_exported_name(mut self, exported_name: impl Into<String>) -> Self {
+        self.exported_name = Some(exported_name.into());
+        self
+    }
+
+    pub fn with_release_location(mut self, release_location: Option<LibraryLocation>) -> Self {
+        self.release_location = release_location;
+        self
+    }
+
+    pub fn with_headers(mut self, header_directory: impl Into<PathBuf>) -> Self {
+        self.header_directories.push(header_directory.into());
+        self
+    }
+
+    fn build_directory(&self, context: &LibraryCompilationContext) -> PathBuf {
+        self.native_library_prefix(context).join("build")
+    }
+
+    /// Generate the `configure` script when the sources only come with `configure.ac`
+    fn ensure_configure_script(
+        &self,
+        context: &LibraryCompilationContext,
+    ) -> Result<(), Box<dyn Error>> {
+        let source_directory = self.source_directory(context);
+        if source_directory.join("configure").exists() {
+            return Ok(());
+        }
+
+        which::which("autoreconf").map_err(|_| {
+            UserFacingError::new("Failed to build project")
+                .reason(format!(
+                    "{} does not contain a configure script and autoreconf is not installed",
+                    source_directory.display()
+                ))
+                .help("Install autoconf, automake and libtool")
+        })?;
+
+        let mut command = Command::new("autoreconf");
+        command
+            .current_dir(&source_directory)
+            .arg("--install")
+            .arg("--force");
+
+        self.run(command, "generate the configure script of")
+    }
+
+    fn configure_command(&self, context: &LibraryCompilationContext) -> Command {
+        let out_dir = self.native_library_prefix(context);
+
+        let mut command = Command::new("sh");
+        command
+            .current_dir(self.build_directory(context))
+            .arg(self.source_directory(context).join("configure"))
+            .arg(format!("--prefix={}", out_dir.display()))
+            .arg(format!("--libdir={}", out_dir.join("lib").display()))
+            .arg(format!("--host={}", context.target()))
+            .arg(format!("--build={}", version_meta().unwrap().host));
+
+        if self.is_static() {
+            command
+                .arg("--enable-static")
+                .arg("--disable-shared")
+                .arg("--with-pic");
+        } else {
+            command.arg("--enable-shared").arg("--disable-static");
+        }
+
+        command.args(&self.configure_flags);
+
+        let mut cpp_flags = self.dependencies.include_headers_flags(context);
+        let mut c_flags = String::new();
+        let ld_flags = self.dependencies.linker_libraries_flags(context);
+
+        if context.is_mac() {
+            let arch = match context.target() {
+                LibraryTarget::AArch64appleDarwin => "arm64",
+                _ => "x86_64",
+            };
+            c_flags = format!(
+                "-arch {} -mmacosx-version-min={}",
+                arch,
+                context.macos_target_version()
+            );
+            command.env("MACOSX_DEPLOYMENT_TARGET", context.macos_target_version());
+        }
+
+        if let Ok(existing) = std::env::var("CPPFLAGS") {
+            cpp_flags = format!("{} {}", existing, cpp_flags);
+        }
+
+        command.env("CPPFLAGS", cpp_flags.trim());
+        command.env("LDFLAGS", format!("{} {}", c_flags, ld_flags).trim());
+        if !c_flags.is_empty() {
+            command.env("CFLAGS", &c_flags);
+            command.env("CXXFLAGS", &c_flags);
+        }
+
+        let mut pkg_config_paths = vec![];
+        if let Ok(ref path) = std::env::var("PKG_CONFIG_PATH") {
+            pkg_config_paths.extend(std::env::split_paths(path));
+        }
+        pkg_config_paths.extend(self.all_pkg_config_directories(context));
+        if let Ok(pkg_config_path) = std::env::join_paths(&pkg_config_paths) {
+            command.env("PKG_CONFIG_PATH", pkg_config_path);
+        }
+
+        for (k, v) in self.all_native_library_vars(context) {
+            command.env(k, v);
+        }
+
+        for (k, v) in self.env_vars.iter() {
+            command.env(k, v);
+        }
+
+        command
+    }
+
+    fn run(&self, mut command: Command, action: &str) -> Result<(), Box<dyn Error>> {
+        println!("{:?}", &command);
+        let status = command.status()?;
+
+        if !status.success() {
+            return Err(Box::new(
+                UserFacingError::new("Failed to build project")
+                    .reason(format!("Could not {} {}", action, self.name()))
+                    .help("Inspect the output above for the failing command"),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl Library for AutotoolsLibrary {
+    fn location(&self) -> &LibraryLocation {
+        &self.source_location
+    }
+
+    fn release_location(&self) -> &LibraryLocation {
+        self.release_location
+            .as_ref()
+            .unwrap_or(&self.source_location)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compiled_library_name(&self) -> CompiledLibraryName {
+        self.compiled_name.clone()
+    }
+
+    fn exported_name(&self) -> &str {
+        self.exported_name.as_deref().unwrap_or_else(|| self.name())
+    }
+
+    fn dependencies(&self) -> Option<&LibraryDependencies> {
+        Some(&self.dependencies)
+    }
+
+    fn options(&self) -> &LibraryOptions {
+        &self.options
+    }
+
+    fn options_mut(&mut self) -> &mut LibraryOptions {
+        &mut self.options
+    }
+
+    fn force_compile(&self, context: &LibraryCompilationContext) -> Result<(), Box<dyn Error>> {
+        let build_directory = self.build_directory(context);
+        if !build_directory.exists() {
+            std::fs::create_dir_all(&build_directory)?;
+        }
+
+        println!(
+            "Building Autotools library for target = {:?} and host = {:?}",
+            &context.target().to_string(),
+            &version_meta().unwrap().host
+        );
+
+        self.ensure_configure_script(context)?;
+        self.run(self.configure_command(context), "configure")?;
+
+        let mut make = Command::new("make");
+        make.current_dir(&build_directory)
+            .arg(format!("-j{}", context.jobs()));
+        self.run(make, "make")?;
+
+        let mut install = Command::new("make");
+        install.current_dir(&build_directory).arg("install");
+        self.run(install, "install")?;
+
+        Ok(())
+    }
+
+    fn compiled_library_directories(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let lib_dir = self.native_library_prefix(context).join("lib");
+        let bin_dir = self.native_library_prefix(context).join("bin");
+        vec![lib_dir, bin_dir]
+    }
+
+    fn ensure_requirements(&self, _context: &LibraryCompilationContext) {
+        which::which("make").expect("Autotools projects require make, make sure it is installed");
+    }
+
+    fn native_library_prefix(&self, context: &LibraryCompilationContext) -> PathBuf {
+        context.build_root().join(self.name())
+    }
+
+    fn native_library_include_headers(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let mut dirs = vec![];
+
+        for header_dir in &self.header_directories {
+            let directory = self.native_library_prefix(context).join(header_dir);
+
+            if directory.exists() {
+                dirs.push(directory);
+            }
+        }
+
+        dirs
+    }
+
+    fn native_library_linker_libraries(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let mut dirs = vec![];
+
+        let directory = self.native_library_prefix(context).join("lib");
+
+        if directory.exists() {
+            dirs.push(directory);
+        }
+
+        dirs
+    }
+
+    fn pkg_config_directory(&self, context: &LibraryCompilationContext) -> Option<PathBuf> {
+        let directory = self
+            .native_library_prefix(context)
+            .join("lib")
+            .join("pkgconfig");
+
+        if directory.exists() {
+            return Some(directory);
+        }
+
+        let directory = self
+            .native_library_prefix(context)
+            .join("share")
+            .join("pkgconfig");
+
+        if directory.exists() {
+            return Some(directory);
+        }
+
+        None
+    }
+
+    fn clone_library(&self) -> Box<dyn Library> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+impl From<AutotoolsLibrary> for Box<dyn Library> {
+    fn from(library: AutotoolsLibrary) -> Self {
+        Box::new(library)
+    }
+}
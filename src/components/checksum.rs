@@ -0,0 +1,54 @@
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+#[cfg(any(feature = "tar-location", feature = "zip-location"))]
+use crate::LibraryCompilationContext;
+#[cfg(feature = "downloader")]
+use user_error::UserFacingError;
+
+/// Compute a lowercase hex sha256 digest of the file
+pub fn sha256_file(path: impl AsRef<Path>) -> Result<String, Box<dyn Error>> {
+    let mut file = File::open(path.as_ref())?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Make sure the downloaded file has the expected sha256 digest.
+/// The file is deleted if it does not match.
+#[cfg(feature = "downloader")]
+pub(crate) fn verify_sha256(path: &Path, expected: &str, url: &str) -> Result<(), Box<dyn Error>> {
+    let actual = sha256_file(path)?;
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        return Ok(());
+    }
+
+    std::fs::remove_file(path)?;
+    Err(Box::new(
+        UserFacingError::new("Failed to build project")
+            .reason(format!("Checksum mismatch of {}", url))
+            .reason(format!("expected sha256 {}", expected.trim()))
+            .reason(format!("actual sha256   {}", actual))
+            .help("Make sure the url points to the expected archive and update the checksum"),
+    ))
+}
+
+/// In the strict mode every downloaded archive must have a checksum
+#[cfg(any(feature = "tar-location", feature = "zip-location"))]
+pub(crate) fn ensure_checksum(
+    checksum: Option<&String>,
+    url: &str,
+    context: &LibraryCompilationContext,
+) -> Result<(), Box<dyn Error>> {
+    if checksum.is_none() && context.strict_checksums() {
+        return Err(Box::new(
+            UserFacingError::new("Failed to build project")
+                .reason(format!("{} has no sha256 checksum", url))
+                .help("Specify the sha256 of the archive or build without strict checksums"),
+        ));
+    }
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::LibraryTarget;
+use crate::{DownloadCache, LibraryTarget, LockMode, UrlMirror};
 use std::path::{Path, PathBuf};
 
 pub const DEFAULT_MACOSX_DEPLOYMENT_TARGET_X86_64: &str = "10.14";
@@ -14,6 +14,12 @@ pub struct LibraryCompilationContext {
     debug: bool,
     macos_target_version: Option<String>,
     android_target_api: Option<String>,
+    lock_mode: LockMode,
+    strict_checksums: bool,
+    download_cache: Option<DownloadCache>,
+    offline: bool,
+    mirrors: Vec<UrlMirror>,
+    prebuilt_public_key: Option<String>,
 }
 
 impl LibraryCompilationContext {
@@ -37,6 +43,12 @@ impl LibraryCompilationContext {
             debug,
             macos_target_version: None,
             android_target_api: None,
+            lock_mode: LockMode::Record,
+            strict_checksums: false,
+            download_cache: None,
+            offline: false,
+            mirrors: vec![],
+            prebuilt_public_key: None,
         }
     }
 
@@ -51,9 +63,88 @@ impl LibraryCompilationContext {
             debug: false,
             macos_target_version: None,
             android_target_api: None,
+            lock_mode: LockMode::Record,
+            strict_checksums: false,
+            download_cache: None,
+            offline: false,
+            mirrors: vec![],
+            prebuilt_public_key: None,
         }
     }
 
+    /// Set how resolved revisions of git sources are recorded in the lockfile
+    pub fn with_lock_mode(mut self, lock_mode: LockMode) -> Self {
+        self.lock_mode = lock_mode;
+        self
+    }
+
+    pub fn lock_mode(&self) -> &LockMode {
+        &self.lock_mode
+    }
+
+    /// Refuse to download archives that have no checksum
+    pub fn with_strict_checksums(mut self, strict_checksums: bool) -> Self {
+        self.strict_checksums = strict_checksums;
+        self
+    }
+
+    pub fn strict_checksums(&self) -> bool {
+        self.strict_checksums
+    }
+
+    /// Use a shared directory for downloaded archives and prebuilt libraries
+    pub fn with_download_cache(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.download_cache = Some(DownloadCache::new(directory));
+        self
+    }
+
+    /// Return the download cache of the context, or the one given by the environment variable
+    pub fn download_cache(&self) -> Option<DownloadCache> {
+        self.download_cache.clone().or_else(DownloadCache::from_env)
+    }
+
+    /// Never access the network, only use existing sources and cached downloads
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Download and clone urls that start with the prefix of the mirror from the mirror instead
+    pub fn with_mirror(mut self, mirror: UrlMirror) -> Self {
+        self.mirrors.push(mirror);
+        self
+    }
+
+    pub fn mirrors(&self) -> &[UrlMirror] {
+        self.mirrors.as_slice()
+    }
+
+    /// Return the urls to try in order, rewritten by the mirror with the longest matching prefix.
+    /// Without a matching mirror the url is returned as is.
+    pub fn mirrored_urls(&self, url: &str) -> Vec<String> {
+        self.mirrors
+            .iter()
+            .filter(|mirror| mirror.matches(url))
+            .max_by_key(|mirror| mirror.prefix().len())
+            .and_then(|mirror| mirror.rewrite(url))
+            .unwrap_or_else(|| vec![url.to_string()])
+    }
+
+    /// Only use prebuilt libraries with a valid minisign signature of the public key,
+    /// given as a base64 encoded key or the contents of a minisign.pub file
+    pub fn with_prebuilt_public_key(mut self, public_key: impl Into<String>) -> Self {
+        self.prebuilt_public_key = Some(public_key.into());
+        self
+    }
+
+    pub fn prebuilt_public_key(&self) -> Option<&str> {
+        self.prebuilt_public_key.as_deref()
+    }
+
     pub fn macos_target_version(&self) -> String {
         self.macos_target_version
             .clone()
@@ -78,6 +169,19 @@ impl LibraryCompilationContext {
             .unwrap_or_else(|| DEFAULT_ANDROID_TARGET_API.to_string())
     }
 
+    /// Return the amount of parallel jobs native build tools (make, ninja) may use
+    pub fn jobs(&self) -> usize {
+        std::env::var("NUM_JOBS")
+            .ok()
+            .and_then(|jobs| jobs.parse::<usize>().ok())
+            .or_else(|| {
+                std::thread::available_parallelism()
+                    .ok()
+                    .map(|jobs| jobs.get())
+            })
+            .unwrap_or(1)
+    }
+
     pub fn sources_root(&self) -> &Path {
         &self.sources_root
     }
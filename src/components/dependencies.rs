@@ -77,6 +77,11 @@ impl LibraryDependencies {
         options: &LibraryCompilationContext,
     ) -> Result<(), Box<dyn Error>> {
         for dependency in &self.dependencies {
+            // a bundle only contains the prefix of its own library, so a dependency is neither
+            // fetched nor compiled only if all of its own dependencies are bundled too
+            if is_bundled_with_dependencies(dependency.as_ref(), options) {
+                continue;
+            }
             if let Some(dependencies) = dependency.dependencies() {
                 dependencies.ensure_sources(options)?;
             }
@@ -85,6 +90,35 @@ impl LibraryDependencies {
         Ok(())
     }
 
+    /// Retrieve prebuilt bundles of all dependencies, return true if every one of them is retrieved.
+    /// The bundle of a dependency is only retrieved once the bundles of its own dependencies are.
+    pub fn retrieve_prebuilt_bundles(&self, options: &LibraryCompilationContext) -> bool {
+        let mut retrieved = true;
+        for dependency in &self.dependencies {
+            retrieved &= dependency
+                .dependencies()
+                .is_none_or(|dependencies| dependencies.retrieve_prebuilt_bundles(options))
+                && dependency.retrieve_prebuilt_bundle(options).is_some();
+        }
+        retrieved
+    }
+
+    /// Return true if all dependencies and their own dependencies are unpacked from prebuilt bundles
+    pub fn have_prebuilt_bundles(&self, options: &LibraryCompilationContext) -> bool {
+        self.dependencies
+            .iter()
+            .all(|dependency| is_bundled_with_dependencies(dependency.as_ref(), options))
+    }
+
+    /// Return names of the dependencies and urls of their sources that are not available offline
+    pub fn unavailable_offline(&self, options: &LibraryCompilationContext) -> Vec<(String, String)> {
+        let mut unavailable = vec![];
+        for dependency in &self.dependencies {
+            unavailable.extend(dependency.unavailable_offline(options));
+        }
+        unavailable
+    }
+
     pub fn ensure_requirements(
         &self,
         options: &LibraryCompilationContext,
@@ -100,6 +134,10 @@ impl LibraryDependencies {
 
     pub fn force_compile(&self, options: &LibraryCompilationContext) -> Result<(), Box<dyn Error>> {
         for dependency in &self.dependencies {
+            if is_bundled_with_dependencies(dependency.as_ref(), options) {
+                println!("Using the prebuilt bundle of {}", dependency.name());
+                continue;
+            }
             if let Some(dependencies) = dependency.dependencies() {
                 dependencies.force_compile(options)?;
             }
@@ -138,6 +176,16 @@ impl LibraryDependencies {
     }
 }
 
+fn is_bundled_with_dependencies(
+    library: &dyn Library,
+    options: &LibraryCompilationContext,
+) -> bool {
+    library.has_prebuilt_bundle(options)
+        && library
+            .dependencies()
+            .is_none_or(|dependencies| dependencies.have_prebuilt_bundles(options))
+}
+
 impl Clone for LibraryDependencies {
     fn clone(&self) -> Self {
         Self {
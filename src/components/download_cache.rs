@@ -0,0 +1,269 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use sha2::{Digest, Sha256};
+
+use crate::sha256_file;
+
+/// An environment variable with a path to the download cache directory
+pub const DOWNLOAD_CACHE_ENV_VAR: &str = "SHARED_LIBRARY_BUILDER_DOWNLOAD_CACHE";
+
+/// A file next to the cached download that contains its url.
+/// Its modification time tells when the download was used last time.
+const URL_FILE_NAME: &str = ".url";
+
+/// A directory with downloaded archives and prebuilt libraries shared between builds.
+/// Downloads are keyed by their url and checksum, so that a changed checksum
+/// never picks a stale file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadCache {
+    directory: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedDownload {
+    pub url: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub last_used: SystemTime,
+}
+
+impl DownloadCache {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// Create a download cache in a directory given by the environment variable, if it is set
+    pub fn from_env() -> Option<Self> {
+        std::env::var(DOWNLOAD_CACHE_ENV_VAR)
+            .ok()
+            .filter(|directory| !directory.trim().is_empty())
+            .map(Self::new)
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn key(url: &str, sha256: Option<&str>) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(url.as_bytes());
+        hasher.update(b"\n");
+        if let Some(sha256) = sha256 {
+            hasher.update(sha256.trim().to_lowercase().as_bytes());
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// Return a path to the cached download of the url.
+    /// A cached file that does not match the checksum is removed.
+    pub fn get(&self, url: &str, sha256: Option<&str>) -> Option<PathBuf> {
+        let entry = self.directory.join(Self::key(url, sha256));
+        let file = Self::entry_file(&entry)?;
+
+        if let Some(sha256) = sha256 {
+            let matches = sha256_file(&file)
+                .map(|actual| actual.eq_ignore_ascii_case(sha256.trim()))
+                .unwrap_or(false);
+            if !matches {
+                std::fs::remove_dir_all(&entry).ok()?;
+                return None;
+            }
+        }
+
+        // remember when the download was used
+        std::fs::write(entry.join(URL_FILE_NAME), url).ok()?;
+        Some(file)
+    }
+
+    /// Copy the downloaded file into the cache and return the path to the cached copy
+    pub fn insert(
+        &self,
+        url: &str,
+        sha256: Option<&str>,
+        file: impl AsRef<Path>,
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        let file = file.as_ref();
+        let key = Self::key(url, sha256);
+        let entry = self.directory.join(&key);
+        let file_name = file
+            .file_name()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(&key));
+
+        // populate a temporary entry first so that concurrent builds never see a partial file
+        let temporary = self
+            .directory
+            .join(format!(".{}-{}", &key, std::process::id()));
+        if temporary.exists() {
+            std::fs::remove_dir_all(&temporary)?;
+        }
+        std::fs::create_dir_all(&temporary)?;
+        std::fs::copy(file, temporary.join(&file_name))?;
+        std::fs::write(temporary.join(URL_FILE_NAME), url)?;
+
+        if entry.exists() {
+            std::fs::remove_dir_all(&entry)?;
+        }
+        if std::fs::rename(&temporary, &entry).is_err() {
+            // another build cached the same download in the meantime
+            std::fs::remove_dir_all(&temporary)?;
+        }
+        Ok(entry.join(file_name))
+    }
+
+    /// All cached downloads
+    pub fn list(&self) -> Result<Vec<CachedDownload>, Box<dyn Error>> {
+        let mut downloads = vec![];
+        if !self.directory.exists() {
+            return Ok(downloads);
+        }
+
+        for entry in self.directory.read_dir()? {
+            let entry = entry?.path();
+            if !entry.is_dir() || Self::is_temporary(&entry) {
+                continue;
+            }
+            let file = match Self::entry_file(&entry) {
+                None => continue,
+                Some(file) => file,
+            };
+            let url_file = entry.join(URL_FILE_NAME);
+            downloads.push(CachedDownload {
+                url: std::fs::read_to_string(&url_file)?,
+                size: file.metadata()?.len(),
+                last_used: url_file.metadata()?.modified()?,
+                path: file,
+            });
+        }
+        downloads.sort_by(|a, b| a.url.cmp(&b.url));
+        Ok(downloads)
+    }
+
+    /// Remove downloads that were not used for the given duration and return them
+    pub fn prune(&self, unused_for: Duration) -> Result<Vec<CachedDownload>, Box<dyn Error>> {
+        let now = SystemTime::now();
+        let mut pruned = vec![];
+
+        for download in self.list()? {
+            let unused = now
+                .duration_since(download.last_used)
+                .unwrap_or(Duration::ZERO);
+            if unused >= unused_for {
+                if let Some(entry) = download.path.parent() {
+                    std::fs::remove_dir_all(entry)?;
+                }
+                pruned.push(download);
+            }
+        }
+        Ok(pruned)
+    }
+
+    /// Remove all cached downloads
+    pub fn clear(&self) -> Result<(), Box<dyn Error>> {
+        if self.directory.exists() {
+            std::fs::remove_dir_all(&self.directory)?;
+        }
+        Ok(())
+    }
+
+    fn entry_file(entry: &Path) -> Option<PathBuf> {
+        entry
+            .read_dir()
+            .ok()?
+            .filter_map(|each| each.ok())
+            .map(|each| each.path())
+            .find(|each| {
+                each.is_file() && each.file_name().is_some_and(|name| name != URL_FILE_NAME)
+            })
+    }
+
+    fn is_temporary(entry: &Path) -> bool {
+        entry
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+    }
+}
+
+/// Download the url into the directory, or copy it from the download cache.
+/// When a checksum is given, the download is verified before it is cached.
+/// Urls that match a mirror of the context are downloaded from the mirrors instead.
+#[cfg(feature = "downloader")]
+pub(crate) fn download_file(
+    url: &str,
+    sha256: Option<&str>,
+    directory: &Path,
+    context: &crate::LibraryCompilationContext,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let cache = context.download_cache();
+
+    if let Some(cached) = cache.as_ref().and_then(|cache| cache.get(url, sha256)) {
+        let file_name = cached.file_name().ok_or("Cached download has no name")?;
+        let destination = directory.join(file_name);
+        std::fs::copy(&cached, &destination)?;
+        println!("Using cached download of {}", url);
+        return Ok(destination);
+    }
+
+    if context.is_offline() {
+        return Err(Box::new(
+            user_error::UserFacingError::new("Failed to build project")
+                .reason(format!("{} is not available offline", url))
+                .help("Build once with network access and a download cache to cache it"),
+        ));
+    }
+
+    // mirrors are tried in order, the last error is reported if all of them fail
+    let mirrored_urls = context.mirrored_urls(url);
+    let mut last_error = None;
+    for mirrored_url in &mirrored_urls {
+        match download_from(mirrored_url, sha256, directory) {
+            Ok(downloaded_path) => {
+                // the download is cached under the original url, independently of the mirror
+                if let Some(cache) = &cache {
+                    cache.insert(url, sha256, &downloaded_path)?;
+                }
+                return Ok(downloaded_path);
+            }
+            Err(error) => {
+                if mirrored_urls.len() > 1 {
+                    println!("Could not download {}: {}", mirrored_url, error);
+                }
+                last_error = Some(error);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| format!("Could not download {}", url).into()))
+}
+
+#[cfg(feature = "downloader")]
+fn download_from(
+    url: &str,
+    sha256: Option<&str>,
+    directory: &Path,
+) -> Result<PathBuf, Box<dyn Error>> {
+    use downloader::{Download, Downloader};
+
+    let download = Download::new(url);
+    let file_name = directory.join(&download.file_name);
+
+    let mut downloader = Downloader::builder().download_folder(directory).build()?;
+    let downloaded_path = match downloader.download(&[download])?.remove(0) {
+        Ok(summary) => summary.file_name,
+        Err(error) => {
+            // remove a partial download, so that the next mirror can be tried
+            if file_name.is_file() {
+                std::fs::remove_file(&file_name)?;
+            }
+            return Err(error.into());
+        }
+    };
+
+    if let Some(sha256) = sha256 {
+        crate::components::verify_sha256(&downloaded_path, sha256, url)?;
+    }
+    Ok(downloaded_path)
+}
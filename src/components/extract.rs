@@ -0,0 +1,408 @@
+use std::error::Error;
+use std::ffi::OsStr;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use user_error::UserFacingError;
+
+/// Leading directories removed from the paths of archive entries when they are unpacked
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StripComponents {
+    /// Unpack the entries as they are
+    #[default]
+    None,
+    /// Remove the given amount of leading directories, entries with a shorter path are skipped
+    Count(usize),
+    /// Flatten the top-level directory when all entries are inside of it, for example `zlib-1.3.1/`
+    Auto,
+}
+
+impl StripComponents {
+    /// Resolve the amount of leading directories to remove from the paths of the entries
+    fn count<'a>(&self, paths: impl IntoIterator<Item = &'a Path>) -> usize {
+        match self {
+            StripComponents::None => 0,
+            StripComponents::Count(count) => *count,
+            StripComponents::Auto => {
+                let mut top_level = None;
+                let mut nested = false;
+                for path in paths {
+                    let mut components = normal_components(path);
+                    let first = match components.next() {
+                        None => continue,
+                        Some(first) => first,
+                    };
+                    if *top_level.get_or_insert(first) != first {
+                        return 0;
+                    }
+                    nested |= components.next().is_some();
+                }
+                usize::from(nested)
+            }
+        }
+    }
+}
+
+/// Maps paths of the archive entries to paths within the source directory.
+/// Only entries inside of the `sources` folder are unpacked.
+#[derive(Debug, Clone)]
+pub(crate) struct EntryPaths {
+    sources: PathBuf,
+    strip_components: StripComponents,
+}
+
+impl EntryPaths {
+    pub(crate) fn new(sources: Option<&Path>, strip_components: StripComponents) -> Self {
+        Self {
+            sources: sources.map(Path::to_path_buf).unwrap_or_default(),
+            strip_components,
+        }
+    }
+
+    /// Components of the entry path inside of the `sources` folder
+    fn relative<'a>(&self, path: &'a Path) -> Option<impl Iterator<Item = &'a OsStr>> {
+        let mut components = normal_components(path);
+        for folder in normal_components(&self.sources) {
+            if components.next()? != folder {
+                return None;
+            }
+        }
+        Some(components)
+    }
+
+    /// Decide how many leading directories to strip, given the paths of all entries
+    fn resolve<'a>(&self, paths: impl IntoIterator<Item = &'a Path>) -> ResolvedEntryPaths<'_> {
+        let relative = paths
+            .into_iter()
+            .filter_map(|path| self.relative(path))
+            .map(|components| components.collect::<PathBuf>())
+            .collect::<Vec<PathBuf>>();
+
+        ResolvedEntryPaths {
+            entry_paths: self,
+            strip: self
+                .strip_components
+                .count(relative.iter().map(PathBuf::as_path)),
+        }
+    }
+
+    #[cfg(feature = "tar-location")]
+    fn needs_all_paths(&self) -> bool {
+        self.strip_components == StripComponents::Auto
+    }
+
+    fn nothing_unpacked_error(&self) -> Box<dyn Error> {
+        let reason = if self.sources.as_os_str().is_empty() {
+            "The archive has no entries to unpack".to_string()
+        } else {
+            format!("The archive does not contain {}", self.sources.display())
+        };
+        Box::new(
+            UserFacingError::new("Failed to build project")
+                .reason(reason)
+                .help("Make sure the sources folder and the stripped components are correct"),
+        )
+    }
+}
+
+struct ResolvedEntryPaths<'a> {
+    entry_paths: &'a EntryPaths,
+    strip: usize,
+}
+
+impl ResolvedEntryPaths<'_> {
+    /// Return the path of the entry within the destination, or None if it is not unpacked
+    fn map(&self, path: &Path) -> Option<PathBuf> {
+        let mapped = self
+            .entry_paths
+            .relative(path)?
+            .skip(self.strip)
+            .collect::<PathBuf>();
+        (!mapped.as_os_str().is_empty()).then_some(mapped)
+    }
+}
+
+/// Prepare the sources in a temporary directory next to the source directory and move them in place
+/// once they are complete, so that an interrupted download or extraction never leaves
+/// a half-populated source directory behind.
+/// The closure receives a temporary directory for the download and a directory to unpack into.
+pub(crate) fn unpack_atomically(
+    source_directory: &Path,
+    unpack: impl FnOnce(&Path, &Path) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let name = source_directory
+        .file_name()
+        .ok_or_else(|| format!("{} has no name", source_directory.display()))?
+        .to_string_lossy()
+        .to_string();
+    let temporary =
+        source_directory.with_file_name(format!(".{}.unpacking-{}", name, std::process::id()));
+    if temporary.exists() {
+        std::fs::remove_dir_all(&temporary)?;
+    }
+    let unpacked = temporary.join("unpacked");
+    std::fs::create_dir_all(&unpacked)?;
+
+    let result = unpack(&temporary, &unpacked)
+        .and_then(|_| Ok(std::fs::rename(&unpacked, source_directory)?));
+
+    // a failed cleanup must not hide whether unpacking succeeded
+    if let Err(error) = std::fs::remove_dir_all(&temporary) {
+        eprintln!("Failed to remove {} due to {}", temporary.display(), error);
+    }
+    result
+}
+
+/// Unpack the tar archive into the destination, refusing entries with absolute paths,
+/// `..` components or links that point outside of the destination.
+/// The archive is read twice when the stripped components are detected automatically.
+#[cfg(feature = "tar-location")]
+pub(crate) fn unpack_tar<R: Read>(
+    open: impl Fn() -> Result<R, Box<dyn Error>>,
+    destination: &Path,
+    entry_paths: &EntryPaths,
+) -> Result<(), Box<dyn Error>> {
+    let mut paths = vec![];
+    if entry_paths.needs_all_paths() {
+        for entry in tar::Archive::new(open()?).entries()? {
+            paths.push(entry?.path()?.to_path_buf());
+        }
+    }
+    let entry_paths = entry_paths.resolve(paths.iter().map(PathBuf::as_path));
+
+    let mut archive = tar::Archive::new(open()?);
+    let destination = destination.canonicalize()?;
+    let mut unpacked = false;
+
+    // directories are created last so that their permissions do not prevent unpacking the content
+    let mut directories = vec![];
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        ensure_enclosed(&path)?;
+        let mapped = match entry_paths.map(&path) {
+            None => continue,
+            Some(mapped) => mapped,
+        };
+        unpacked = true;
+
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            directories.push((path, mapped, entry));
+            continue;
+        }
+
+        let output = enclosed_output(&destination, &path, &mapped)?;
+        let link = entry.link_name()?.map(|link| link.to_path_buf());
+        match link {
+            Some(link) if entry_type.is_hard_link() => {
+                // hard links are relative to the root of the archive
+                ensure_enclosed(&link)?;
+                let target = entry_paths
+                    .map(&link)
+                    .ok_or_else(|| unsafe_entry_error(&path, "links outside of the sources"))?;
+                std::fs::hard_link(destination.join(target), &output)?;
+            }
+            Some(link) if entry_type.is_symlink() => {
+                ensure_enclosed_link(&destination, &path, &output, &link)?;
+                entry.unpack(&output)?;
+            }
+            _ => {
+                entry.unpack(&output)?;
+            }
+        }
+    }
+
+    if !unpacked {
+        return Err(entry_paths.entry_paths.nothing_unpacked_error());
+    }
+
+    directories.sort_by(|(_, a, _), (_, b, _)| b.cmp(a));
+    for (path, mapped, mut directory) in directories {
+        directory.unpack(enclosed_output(&destination, &path, &mapped)?)?;
+    }
+    Ok(())
+}
+
+/// Extract the zip archive into the destination, refusing entries with absolute paths,
+/// `..` components or symbolic links that point outside of the destination.
+/// Unix permissions of the entries are preserved.
+#[cfg(feature = "zip-location")]
+pub(crate) fn extract_zip(
+    zip_file: &Path,
+    destination: &Path,
+    entry_paths: &EntryPaths,
+) -> Result<(), Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(zip_file)?)?;
+    let names = archive
+        .file_names()
+        .map(PathBuf::from)
+        .collect::<Vec<PathBuf>>();
+    let entry_paths = entry_paths.resolve(names.iter().map(PathBuf::as_path));
+    let destination = destination.canonicalize()?;
+    let mut unpacked = false;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let path = PathBuf::from(entry.name());
+        ensure_enclosed(&path)?;
+        let mapped = match entry_paths.map(&path) {
+            None => continue,
+            Some(mapped) => mapped,
+        };
+        unpacked = true;
+
+        let output = enclosed_output(&destination, &path, &mapped)?;
+        if entry.is_dir() {
+            std::fs::create_dir_all(&output)?;
+            continue;
+        }
+
+        if entry.is_symlink() {
+            let mut link = String::new();
+            entry.read_to_string(&mut link)?;
+            ensure_enclosed_link(&destination, &path, &output, Path::new(&link))?;
+            create_symlink(Path::new(&link), &output)?;
+            continue;
+        }
+
+        std::io::copy(&mut entry, &mut std::fs::File::create(&output)?)?;
+
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&output, std::fs::Permissions::from_mode(mode & 0o777))?;
+        }
+    }
+
+    if !unpacked {
+        return Err(entry_paths.entry_paths.nothing_unpacked_error());
+    }
+    Ok(())
+}
+
+#[cfg(all(feature = "zip-location", unix))]
+fn create_symlink(link: &Path, path: &Path) -> Result<(), Box<dyn Error>> {
+    std::os::unix::fs::symlink(link, path)?;
+    Ok(())
+}
+
+/// Creating symbolic links requires special privileges on windows, so the target file is copied instead
+#[cfg(all(feature = "zip-location", not(unix)))]
+fn create_symlink(link: &Path, path: &Path) -> Result<(), Box<dyn Error>> {
+    let target = path.parent().unwrap_or(path).join(link);
+    if target.is_file() {
+        std::fs::copy(target, path)?;
+    }
+    Ok(())
+}
+
+fn normal_components(path: &Path) -> impl Iterator<Item = &OsStr> {
+    path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(name),
+        _ => None,
+    })
+}
+
+/// Entries must be relative and must not contain `..`
+fn ensure_enclosed(path: &Path) -> Result<(), Box<dyn Error>> {
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => {
+                return Err(unsafe_entry_error(path, "is an absolute path"))
+            }
+            Component::ParentDir => return Err(unsafe_entry_error(path, "contains ..")),
+            Component::CurDir | Component::Normal(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Create the parent directories of the entry and return the path to unpack it to.
+/// Previously unpacked symbolic links must not lead the entry outside of the destination.
+fn enclosed_output(
+    destination: &Path,
+    path: &Path,
+    mapped: &Path,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let output = destination.join(mapped);
+    let parent = output.parent().unwrap_or(destination);
+    std::fs::create_dir_all(parent)?;
+
+    let parent = parent.canonicalize()?;
+    if !parent.starts_with(destination) {
+        return Err(unsafe_entry_error(path, "is outside of the destination"));
+    }
+    Ok(match output.file_name() {
+        Some(name) => parent.join(name),
+        None => parent,
+    })
+}
+
+/// A link target resolved against the directory of the link must stay within the destination.
+/// The target is walked through the unpacked files, so that links unpacked earlier
+/// can not lead it outside of the destination. A `..` must not follow a path that is not
+/// unpacked yet, because a later entry could turn that path into a link.
+fn ensure_enclosed_link(
+    destination: &Path,
+    path: &Path,
+    output: &Path,
+    link: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut resolved = output.parent().unwrap_or(destination).to_path_buf();
+    let mut unpacked = true;
+
+    for component in link.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => {
+                return Err(unsafe_entry_error(
+                    path,
+                    &format!("links to an absolute path {}", link.display()),
+                ))
+            }
+            Component::ParentDir => {
+                if !unpacked {
+                    return Err(unsafe_entry_error(
+                        path,
+                        &format!(
+                            "links through a path that is not unpacked yet {}",
+                            link.display()
+                        ),
+                    ));
+                }
+                resolved.pop();
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+                if unpacked {
+                    match resolved.canonicalize() {
+                        Ok(canonical) => resolved = canonical,
+                        Err(_) => unpacked = false,
+                    }
+                }
+            }
+            Component::CurDir => {}
+        }
+
+        if !resolved.starts_with(destination) {
+            return Err(unsafe_entry_error(
+                path,
+                &format!("links outside of the destination to {}", link.display()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn unsafe_entry_error(path: &Path, reason: &str) -> Box<dyn Error> {
+    Box::new(
+        UserFacingError::new("Failed to build project")
+            .reason(format!(
+                "Refusing to unpack {} because it {}",
+                path.display(),
+                reason
+            ))
+            .help("Make sure the archive comes from a trusted source"),
+    )
+}
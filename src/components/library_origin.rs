@@ -0,0 +1,90 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::sha256_file;
+
+/// The extension of a file next to an exported library that records where the library comes from
+pub const LIBRARY_ORIGIN_EXTENSION: &str = "origin";
+
+/// Where an exported library comes from. It is recorded next to the exported library,
+/// so that a library from a different origin, for example an older tag, is not reused.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum LibraryOrigin {
+    /// Retrieved as a prebuilt asset, the source identifies the release, for example its url
+    Prebuilt { source: String, asset: String },
+    /// Compiled from sources with the fingerprint of their location
+    /// and the revision they are checked out at, for example the commit of the latest version
+    Compiled {
+        fingerprint: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        revision: Option<String>,
+    },
+}
+
+/// The contents of the origin file, stored as toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedOrigin {
+    /// sha256 digest of the exported library when the origin was recorded
+    sha256: String,
+    origin: LibraryOrigin,
+}
+
+impl LibraryOrigin {
+    pub fn prebuilt(source: impl Into<String>, asset: impl Into<String>) -> Self {
+        Self::Prebuilt {
+            source: source.into(),
+            asset: asset.into(),
+        }
+    }
+
+    pub fn compiled(fingerprint: impl Into<String>, revision: Option<String>) -> Self {
+        Self::Compiled {
+            fingerprint: fingerprint.into(),
+            revision,
+        }
+    }
+
+    /// Return the path of the origin file of the exported library
+    pub fn path(exported_library: &Path) -> PathBuf {
+        let mut file_name = exported_library
+            .file_name()
+            .unwrap_or_default()
+            .to_os_string();
+        file_name.push(".");
+        file_name.push(LIBRARY_ORIGIN_EXTENSION);
+        exported_library.with_file_name(file_name)
+    }
+
+    /// Read the recorded origin of the exported library, or None if it is not recorded
+    /// or the library was modified since then
+    pub fn read(exported_library: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(Self::path(exported_library)).ok()?;
+        let recorded: RecordedOrigin = toml::from_str(&contents).ok()?;
+
+        let sha256 = sha256_file(exported_library).ok()?;
+        sha256
+            .eq_ignore_ascii_case(&recorded.sha256)
+            .then_some(recorded.origin)
+    }
+
+    /// Return true if the exported library exists and comes from this origin
+    pub fn is_current(&self, exported_library: &Path) -> bool {
+        Self::read(exported_library).is_some_and(|origin| &origin == self)
+    }
+
+    /// Record that the exported library comes from this origin
+    pub fn record(&self, exported_library: &Path) -> Result<(), Box<dyn Error>> {
+        let recorded = RecordedOrigin {
+            sha256: sha256_file(exported_library)?,
+            origin: self.clone(),
+        };
+        std::fs::write(
+            Self::path(exported_library),
+            toml::to_string_pretty(&recorded)?,
+        )?;
+        Ok(())
+    }
+}
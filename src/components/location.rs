@@ -1,8 +1,9 @@
-use crate::{Library, LibraryCompilationContext, PathLocation};
+use crate::{Library, LibraryCompilationContext, LibraryPatch, PathLocation, PrebuiltUrlTemplate};
 use std::error::Error;
 use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LibraryLocation {
@@ -47,9 +48,24 @@ impl LibraryLocation {
         default_source_directory: &Path,
         context: &LibraryCompilationContext,
     ) -> Option<PathBuf> {
-        // Static libraries must be compiled from sources
+        // Static libraries are linked together with their headers and dependencies,
+        // so they are only retrieved as prebuilt bundles of the whole prefix
         if library.is_static() {
-            return None;
+            let dependencies_retrieved = library
+                .dependencies()
+                .is_none_or(|dependencies| dependencies.retrieve_prebuilt_bundles(context));
+            if !dependencies_retrieved {
+                return None;
+            }
+            self.retrieve_prebuilt_bundle(library.clone_library(), context)?;
+            return library.find_compiled_library(context);
+        }
+
+        #[cfg(feature = "downloader")]
+        if let Some(prebuilt_library) = self.prebuilt_url_template().and_then(|prebuilt_url| {
+            prebuilt_url.retrieve_prebuilt_library(library.as_ref(), self.version_name(), context)
+        }) {
+            return Some(prebuilt_library);
         }
 
         match self {
@@ -67,10 +83,159 @@ impl LibraryLocation {
         }
     }
 
+    /// Try to retrieve a prebuilt bundle of the native library prefix for the current target,
+    /// unpack it and return the prefix
+    #[allow(unused_variables)]
+    pub fn retrieve_prebuilt_bundle(
+        &self,
+        library: Box<dyn Library>,
+        context: &LibraryCompilationContext,
+    ) -> Option<PathBuf> {
+        #[cfg(feature = "tar-location")]
+        {
+            let prefix = self
+                .prebuilt_url_template()
+                .and_then(|prebuilt_url| {
+                    prebuilt_url.retrieve_prebuilt_bundle(
+                        library.as_ref(),
+                        self.version_name(),
+                        context,
+                    )
+                })
+                .or_else(|| match self {
+                    #[cfg(feature = "git-location")]
+                    LibraryLocation::Git(git_location) => {
+                        git_location.retrieve_prebuilt_bundle(library.as_ref(), context)
+                    }
+                    LibraryLocation::Path(path_location) => {
+                        path_location.retrieve_prebuilt_bundle(library.as_ref(), context)
+                    }
+                    _ => None,
+                });
+
+            // a bundle that was unpacked before must not be mistaken for the current one
+            if prefix.is_none() {
+                let marker = library
+                    .native_library_prefix(context)
+                    .join(crate::PREBUILT_BUNDLE_MARKER);
+                if marker.exists() {
+                    std::fs::remove_file(marker).ok();
+                }
+            }
+            prefix
+        }
+
+        #[cfg(not(feature = "tar-location"))]
+        None
+    }
+
+    /// A url of prebuilt libraries that are downloaded instead of compiling the library
+    pub fn prebuilt_url_template(&self) -> Option<&PrebuiltUrlTemplate> {
+        match self {
+            #[cfg(feature = "git-location")]
+            LibraryLocation::Git(git_location) => git_location.prebuilt_url_template(),
+            LibraryLocation::Path(path_location) => path_location.prebuilt_url_template(),
+            #[cfg(feature = "tar-location")]
+            LibraryLocation::Tar(tar_location) => tar_location.prebuilt_url_template(),
+            #[cfg(feature = "zip-location")]
+            LibraryLocation::Zip(zip_location) => zip_location.prebuilt_url_template(),
+        }
+    }
+
+    /// The version of the location, if it has one, for example a git tag
+    pub fn version_name(&self) -> Option<&str> {
+        match self {
+            #[cfg(feature = "git-location")]
+            LibraryLocation::Git(git_location) => git_location.version_name(),
+            _ => None,
+        }
+    }
+
+    /// Patches applied to the sources after they are fetched, in order
+    pub fn patches(&self) -> &[LibraryPatch] {
+        match self {
+            #[cfg(feature = "git-location")]
+            LibraryLocation::Git(git_location) => git_location.patches(),
+            LibraryLocation::Path(path_location) => path_location.patches(),
+            #[cfg(feature = "tar-location")]
+            LibraryLocation::Tar(tar_location) => tar_location.patches(),
+            #[cfg(feature = "zip-location")]
+            LibraryLocation::Zip(zip_location) => zip_location.patches(),
+        }
+    }
+
+    /// Return the url of the sources if they can not be fetched without network access
+    #[allow(unused_variables)]
+    pub fn unavailable_offline(
+        &self,
+        default_source_directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Option<String> {
+        match self {
+            #[cfg(feature = "git-location")]
+            LibraryLocation::Git(git_location) => {
+                git_location.unavailable_offline(default_source_directory, context)
+            }
+            LibraryLocation::Path(_) => None,
+            #[cfg(feature = "tar-location")]
+            LibraryLocation::Tar(tar_location) => {
+                tar_location.unavailable_offline(default_source_directory, context)
+            }
+            #[cfg(feature = "zip-location")]
+            LibraryLocation::Zip(zip_location) => {
+                zip_location.unavailable_offline(default_source_directory, context)
+            }
+        }
+    }
+
+    /// Return the revision the sources are checked out at, if the location has revisions
+    #[allow(unused_variables)]
+    pub fn resolved_revision(
+        &self,
+        default_source_directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Option<String> {
+        match self {
+            #[cfg(feature = "git-location")]
+            LibraryLocation::Git(git_location) => {
+                git_location.resolved_revision(default_source_directory, context)
+            }
+            _ => None,
+        }
+    }
+
+    /// A sha256 digest that identifies the sources, including the contents of the patches.
+    /// The location is hashed in its serialized form, which is as stable as the configuration files.
+    pub fn fingerprint(&self) -> Result<String, Box<dyn Error>> {
+        let mut hasher = Sha256::new();
+        hasher.update(toml::to_string(self)?.as_bytes());
+        for patch in self.patches() {
+            hasher.update(patch.fingerprint()?.as_bytes());
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    /// Fetch the sources and apply the patches that are not applied yet
     pub fn ensure_sources(
         &self,
         default_source_directory: &Path,
         context: &LibraryCompilationContext,
+    ) -> Result<(), Box<dyn Error>> {
+        self.fetch_sources(default_source_directory, context)?;
+
+        if !self.patches().is_empty() {
+            crate::components::apply_patches(
+                self.patches(),
+                &self.sources_directory(default_source_directory, context),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn fetch_sources(
+        &self,
+        default_source_directory: &Path,
+        context: &LibraryCompilationContext,
     ) -> Result<(), Box<dyn Error>> {
         match self {
             #[cfg(feature = "git-location")]
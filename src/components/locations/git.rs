@@ -7,19 +7,37 @@ use serde::{Deserialize, Serialize};
 use url::Url;
 use user_error::UserFacingError;
 
-use crate::{Library, LibraryCompilationContext};
+use crate::{
+    Library, LibraryCompilationContext, LibraryPatch, LockMode, Lockfile, PrebuiltUrlTemplate,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GitLocation {
     repository: GitRepository,
     version: GitVersion,
     directory: Option<PathBuf>,
+    #[serde(default)]
+    shallow: bool,
+    #[serde(default)]
+    blobless: bool,
+    #[serde(default)]
+    sparse: Vec<PathBuf>,
+    #[serde(default)]
+    submodules: GitSubmodules,
+    #[serde(default)]
+    clean: GitCleanPolicy,
+    #[serde(default)]
+    patches: Vec<LibraryPatch>,
+    #[serde(default)]
+    prebuilt_url: Option<PrebuiltUrlTemplate>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GitRepository {
     GitHub(String, String),
     GitLab(String, String),
+    /// Any url supported by git: https, ssh, `git@host:path` or `file://`
+    Url(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +48,58 @@ pub enum GitVersion {
     Latest,
 }
 
+/// Which submodules to initialize and update after checking out the requested version
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum GitSubmodules {
+    #[default]
+    None,
+    All {
+        recursive: bool,
+    },
+    /// Only submodules with the given paths relative to the repository
+    Paths {
+        paths: Vec<PathBuf>,
+        recursive: bool,
+    },
+}
+
+/// What to do with local changes in an existing sources directory before checking out
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum GitCleanPolicy {
+    /// Remove untracked and ignored files (`git clean -fdx`)
+    Clean,
+    /// Keep untracked files and local modifications as long as git can carry them over
+    #[default]
+    Keep,
+    /// Refuse to check out when tracked files are modified
+    FailIfDirty,
+}
+
+impl GitSubmodules {
+    pub fn all() -> Self {
+        Self::All { recursive: false }
+    }
+
+    pub fn paths(paths: Vec<impl Into<PathBuf>>) -> Self {
+        Self::Paths {
+            paths: paths.into_iter().map(|each| each.into()).collect(),
+            recursive: false,
+        }
+    }
+
+    /// Also update submodules of the submodules
+    pub fn recursive(self) -> Self {
+        match self {
+            Self::None => Self::None,
+            Self::All { .. } => Self::All { recursive: true },
+            Self::Paths { paths, .. } => Self::Paths {
+                paths,
+                recursive: true,
+            },
+        }
+    }
+}
+
 impl GitRepository {
     pub fn github(owner: impl Into<String>, repo: impl Into<String>) -> Self {
         Self::GitHub(owner.into(), repo.into())
@@ -39,14 +109,65 @@ impl GitRepository {
         Self::GitLab(owner.into(), repo.into())
     }
 
+    pub fn url(url: impl Into<String>) -> Self {
+        Self::Url(url.into())
+    }
+
+    /// Return the url of the repository, paths must be absolute.
+    /// Panics if the repository is neither a url nor an absolute path, see [`Self::try_as_url`]
     pub fn as_url(&self) -> Url {
-        Url::parse(self.to_string().as_str()).unwrap()
+        self.try_as_url()
+            .unwrap_or_else(|_| panic!("{} is not a valid git repository url", self))
+    }
+
+    /// Return the url of the repository, or an error if it is neither a url nor an absolute path
+    pub fn try_as_url(&self) -> Result<Url, Box<dyn Error>> {
+        let url = self.to_string();
+
+        if let Ok(url) = Url::parse(&url) {
+            // windows drive letters are parsed as a scheme
+            if url.scheme().len() > 1 {
+                return Ok(url);
+            }
+        }
+
+        // scp-like syntax, for example git@host:owner/repo.git
+        if let Some((authority, path)) = url.split_once(':') {
+            if !authority.contains('/') && !authority.contains('\\') && authority.len() > 1 {
+                if let Ok(url) = Url::parse(&format!(
+                    "ssh://{}/{}",
+                    authority,
+                    path.trim_start_matches('/')
+                )) {
+                    return Ok(url);
+                }
+            }
+        }
+
+        Url::from_file_path(&url).map_err(|_| {
+            UserFacingError::new("Invalid git repository")
+                .reason(format!("{} is neither a url nor an absolute path", url))
+                .help("Use a url or an absolute path to the git repository")
+                .into()
+        })
+    }
+
+    /// Return the url of the repository to report, or the repository as is if it is not a valid url
+    fn display_url(&self) -> String {
+        self.try_as_url()
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| self.to_string())
     }
 
     pub fn repository_name(&self) -> &str {
         match self {
             GitRepository::GitHub(_, name) => name.as_str(),
             GitRepository::GitLab(_, name) => name.as_str(),
+            GitRepository::Url(url) => {
+                let path = url.trim_end_matches(['/', '\\']);
+                let name = path.rsplit(['/', '\\', ':']).next().unwrap_or(path);
+                name.strip_suffix(".git").unwrap_or(name)
+            }
         }
     }
 }
@@ -61,17 +182,35 @@ impl Display for GitRepository {
             GitRepository::GitLab(owner, repo) => {
                 format!("https://gitlab.com/{}/{}", owner, repo)
             }
+
+            GitRepository::Url(url) => url.clone(),
         };
         write!(f, "{}", str)
     }
 }
 
+/// Create a git command that runs in the given directory and never asks for credentials
+fn git(directory: &Path) -> Command {
+    let mut command = Command::new("git");
+    command
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .current_dir(directory);
+    command
+}
+
 impl GitLocation {
     pub fn new(repository: GitRepository) -> Self {
         Self {
             repository,
             version: GitVersion::Latest,
             directory: None,
+            shallow: false,
+            blobless: false,
+            sparse: vec![],
+            submodules: GitSubmodules::None,
+            clean: GitCleanPolicy::Keep,
+            patches: vec![],
+            prebuilt_url: None,
         }
     }
 
@@ -83,46 +222,107 @@ impl GitLocation {
         Self::new(GitRepository::gitlab(owner, repo))
     }
 
+    pub fn url(url: impl Into<String>) -> Self {
+        Self::new(GitRepository::url(url))
+    }
+
     pub fn commit(self, commit: impl Into<String>) -> Self {
         Self {
-            repository: self.repository,
             version: GitVersion::Commit(commit.into()),
-            directory: self.directory,
+            ..self
         }
     }
 
     pub fn branch(self, branch: impl Into<String>) -> Self {
         Self {
-            repository: self.repository,
             version: GitVersion::Branch(branch.into()),
-            directory: self.directory,
+            ..self
         }
     }
 
     pub fn tag(self, tag: impl Into<String>) -> Self {
         Self {
-            repository: self.repository,
             version: GitVersion::Tag(tag.into()),
-            directory: self.directory,
+            ..self
         }
     }
 
     pub fn tag_or_latest(self, tag: Option<impl Into<String>>) -> Self {
         let version = tag
             .map(|tag| GitVersion::Tag(tag.into()))
-            .unwrap_or_else(|| GitVersion::Latest);
-        Self {
-            repository: self.repository,
-            version,
-            directory: self.directory,
-        }
+            .unwrap_or(GitVersion::Latest);
+        Self { version, ..self }
     }
 
     pub fn directory(self, directory: impl Into<PathBuf>) -> Self {
         Self {
-            repository: self.repository,
-            version: self.version,
             directory: Some(directory.into()),
+            ..self
+        }
+    }
+
+    /// Only fetch the requested tag, commit or branch without its history (`--depth 1`)
+    pub fn shallow(self) -> Self {
+        Self {
+            shallow: true,
+            ..self
+        }
+    }
+
+    /// Make a partial clone that downloads file contents only when they are checked out
+    /// (`--filter=blob:none`)
+    pub fn blobless(self) -> Self {
+        Self {
+            blobless: true,
+            ..self
+        }
+    }
+
+    /// Only check out the given directories of the repository (and the files in the root)
+    pub fn sparse(self, directories: Vec<impl Into<PathBuf>>) -> Self {
+        Self {
+            sparse: directories.into_iter().map(|each| each.into()).collect(),
+            ..self
+        }
+    }
+
+    pub fn submodules(self, submodules: GitSubmodules) -> Self {
+        Self { submodules, ..self }
+    }
+
+    pub fn clean_policy(self, clean: GitCleanPolicy) -> Self {
+        Self { clean, ..self }
+    }
+
+    /// Apply a patch to the sources after checkout.
+    /// Applied patches are reverted before checking out another version.
+    pub fn patch(mut self, patch: LibraryPatch) -> Self {
+        self.patches.push(patch);
+        self
+    }
+
+    /// Download prebuilt libraries from the url instead of compiling them
+    pub fn prebuilt_url(self, prebuilt_url: PrebuiltUrlTemplate) -> Self {
+        Self {
+            prebuilt_url: Some(prebuilt_url),
+            ..self
+        }
+    }
+
+    pub(crate) fn patches(&self) -> &[LibraryPatch] {
+        &self.patches
+    }
+
+    pub(crate) fn prebuilt_url_template(&self) -> Option<&PrebuiltUrlTemplate> {
+        self.prebuilt_url.as_ref()
+    }
+
+    /// The tag or commit of the location, it is the default version of prebuilt libraries
+    pub(crate) fn version_name(&self) -> Option<&str> {
+        match &self.version {
+            GitVersion::Tag(tag) => Some(tag.as_str()),
+            GitVersion::Commit(commit) => Some(commit.as_str()),
+            GitVersion::Branch(_) | GitVersion::Latest => None,
         }
     }
 
@@ -145,84 +345,638 @@ impl GitLocation {
         context: &LibraryCompilationContext,
     ) -> Result<(), Box<dyn Error>> {
         let source_directory = self.sources_directory(default_source_directory, context);
+        let lockfile_path = Lockfile::path(context);
+        let locked_name = self.locked_name(&source_directory, context);
+
+        self.pinned_location(&source_directory, context)?
+            .checkout_sources(&source_directory, context)?;
+        if context.lock_mode() == &LockMode::Locked {
+            return Ok(());
+        }
+
+        let revision = self
+            .resolved_revision(default_source_directory, context)
+            .ok_or_else(|| {
+                UserFacingError::new("Failed to build project")
+                    .reason(format!(
+                        "Could not resolve the revision of {}",
+                        source_directory.display()
+                    ))
+                    .help("Make sure the sources directory is a git repository")
+            })?;
+
+        // the lockfile always records the revision that is checked out
+        let mut lockfile = Lockfile::read(&lockfile_path)?;
+        let repository = self.repository.to_string();
+        let version = self.locked_version();
+        let should_record = lockfile.revision(&locked_name).is_none_or(|locked| {
+            locked.repository != repository
+                || locked.version != version
+                || locked.revision != revision
+        });
+
+        if should_record {
+            lockfile.record(locked_name, repository, version, revision);
+            lockfile.write(&lockfile_path)?;
+        }
+        Ok(())
+    }
+
+    /// Return a location that checks out the revision to build: the locked one when building
+    /// with --locked, or when a branch or the latest version is already locked and not updated
+    fn pinned_location(
+        &self,
+        source_directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Result<Self, Box<dyn Error>> {
+        match (context.lock_mode(), &self.version) {
+            (LockMode::Locked, _) => self.locked_location(source_directory, context),
+            (LockMode::Record, GitVersion::Latest | GitVersion::Branch(_)) => Ok(self
+                .locked_location(source_directory, context)
+                .unwrap_or_else(|_| self.clone())),
+            _ => Ok(self.clone()),
+        }
+    }
+
+    /// Describe the requested version in the lockfile, a locked revision is only checked out
+    /// for the version it is resolved from
+    fn locked_version(&self) -> String {
+        match &self.version {
+            GitVersion::Tag(tag) => format!("tag {}", tag),
+            GitVersion::Commit(commit) => format!("commit {}", commit),
+            GitVersion::Branch(branch) => format!("branch {}", branch),
+            GitVersion::Latest => "latest".to_string(),
+        }
+    }
+
+    /// Return a location that checks out the revision recorded in the lockfile
+    fn locked_location(
+        &self,
+        source_directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Result<Self, Box<dyn Error>> {
+        let lockfile_path = Lockfile::path(context);
+        let locked_name = self.locked_name(source_directory, context);
+        let lockfile = Lockfile::read(&lockfile_path)?;
+        let locked = lockfile
+            .revision(&locked_name)
+            .filter(|locked| {
+                locked.repository == self.repository.to_string()
+                    && locked.version == self.locked_version()
+            })
+            .ok_or_else(|| {
+                UserFacingError::new("Failed to build project")
+                    .reason(format!(
+                        "{} of {} at {} is not locked in {}",
+                        &locked_name,
+                        &self.repository,
+                        self.locked_version(),
+                        lockfile_path.display()
+                    ))
+                    .help("Build without --locked to record the revision in the lockfile")
+            })?;
+        Ok(self.clone().commit(&locked.revision))
+    }
+
+    /// Return the repository and version if they are not available without network access
+    pub(crate) fn unavailable_offline(
+        &self,
+        default_source_directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Option<String> {
+        let source_directory = self.sources_directory(default_source_directory, context);
+        if !source_directory.exists() {
+            return Some(self.repository.display_url());
+        }
+
+        let location = match self.pinned_location(&source_directory, context) {
+            Ok(location) => location,
+            Err(_) => return Some(self.repository.display_url()),
+        };
+
+        let revision = match &location.version {
+            GitVersion::Tag(tag) => format!("refs/tags/{}", tag),
+            GitVersion::Commit(commit) => commit.clone(),
+            GitVersion::Branch(branch) => branch.clone(),
+            GitVersion::Latest => "HEAD".to_string(),
+        };
+        let available = git(&source_directory)
+            .arg("rev-parse")
+            .arg("--verify")
+            .arg("--quiet")
+            .arg(format!("{}^{{commit}}", revision))
+            .output()
+            .is_ok_and(|output| output.status.success());
+
+        (!available).then(|| {
+            format!(
+                "{} ({:?})",
+                self.repository.display_url(),
+                &location.version
+            )
+        })
+    }
+
+    /// Return the commit checked out in the sources directory
+    pub fn resolved_revision(
+        &self,
+        default_source_directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Option<String> {
+        let source_directory = self.sources_directory(default_source_directory, context);
+        git(&source_directory)
+            .arg("rev-parse")
+            .arg("HEAD")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Libraries are locked by their sources directory relative to the sources root
+    fn locked_name(&self, source_directory: &Path, context: &LibraryCompilationContext) -> String {
+        source_directory
+            .strip_prefix(context.sources_root())
+            .unwrap_or(source_directory)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+
+    fn checkout_sources(
+        &self,
+        source_directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Result<(), Box<dyn Error>> {
+        let source_directory = source_directory.to_path_buf();
+        let offline = context.is_offline();
 
         if !source_directory.exists() {
-            let result = Command::new("git")
-                .env("GIT_TERMINAL_PROMPT", "0")
-                .arg("clone")
-                .arg(self.repository.to_string())
-                .arg(&source_directory)
-                .status()
-                .unwrap();
-
-            if !result.success() {
+            if offline {
+                return Err(Box::new(
+                    UserFacingError::new("Failed to build project")
+                        .reason(format!(
+                            "{} is not available offline",
+                            &self.repository.display_url()
+                        ))
+                        .help("Build once with network access to clone the repository"),
+                ));
+            }
+            self.clone_repository(&source_directory, context)?;
+        }
+
+        // patches are applied again once the requested version is checked out
+        crate::components::revert_patches(&source_directory)?;
+
+        if !self.sparse.is_empty() {
+            let status = git(&source_directory)
+                .arg("sparse-checkout")
+                .arg("set")
+                .args(&self.sparse)
+                .status()?;
+            if !status.success() {
+                return Err(self.checkout_error());
+            }
+
+            // a sparse clone is not checked out, and pulling the latest version
+            // that is already cloned would leave the working tree empty
+            if !self.is_checked_out(&source_directory) && self.has_head(&source_directory) {
+                let status = git(&source_directory)
+                    .arg("read-tree")
+                    .arg("-mu")
+                    .arg("HEAD")
+                    .status()?;
+                if !status.success() {
+                    return Err(self.checkout_error());
+                }
+            }
+        }
+
+        let modified_files = self.modified_files(&source_directory)?;
+        if !modified_files.is_empty() {
+            if self.clean == GitCleanPolicy::FailIfDirty {
                 return Err(Box::new(
                     UserFacingError::new("Failed to build project")
-                        .reason(format!("Could not clone {}", &self.repository.as_url()))
-                        .help(
-                            "Make sure the configuration is correct and the git repository exists",
-                        ),
+                        .reason(format!(
+                            "{} has local modifications: {}",
+                            source_directory.display(),
+                            modified_files.join(", ")
+                        ))
+                        .help("Commit, stash or revert the changes before building"),
                 ));
             }
+            println!(
+                "Keeping local modifications in {}: {}",
+                source_directory.display(),
+                modified_files.join(", ")
+            );
         }
 
-        Command::new("git")
-            .current_dir(&source_directory)
-            .arg("clean")
-            .arg("-fdx")
-            .status()
-            .unwrap();
+        if self.clean == GitCleanPolicy::Clean {
+            git(&source_directory).arg("clean").arg("-fdx").status()?;
+        }
 
-        Command::new("git")
-            .env("GIT_TERMINAL_PROMPT", "0")
-            .current_dir(&source_directory)
-            .arg("fetch")
-            .arg("--all")
-            .arg("--tags")
-            .status()
-            .unwrap();
+        if offline {
+            println!("Offline, not fetching {}", &self.repository);
+        } else {
+            self.fetch(&source_directory, context)?;
+        }
 
         let status = match &self.version {
-            GitVersion::Tag(tag) => Command::new("git")
-                .current_dir(&source_directory)
+            GitVersion::Tag(tag) => git(&source_directory)
                 .arg("checkout")
                 .arg(format!("tags/{}", tag))
-                .status()
-                .unwrap(),
-            GitVersion::Commit(commit) => Command::new("git")
-                .current_dir(&source_directory)
+                .status()?,
+            GitVersion::Commit(commit) => git(&source_directory)
                 .arg("checkout")
                 .arg(commit)
-                .status()
-                .unwrap(),
-            GitVersion::Branch(branch) => Command::new("git")
-                .current_dir(&source_directory)
+                .status()?,
+            GitVersion::Branch(branch) => git(&source_directory)
                 .arg("checkout")
                 .arg(branch)
-                .status()
-                .unwrap(),
-            GitVersion::Latest => Command::new("git")
-                .env("GIT_TERMINAL_PROMPT", "0")
-                .current_dir(&source_directory)
-                .arg("pull")
-                .status()
-                .unwrap(),
+                .status()?,
+            // without network the latest version is the one we already have
+            GitVersion::Latest if offline => git(&source_directory)
+                .arg("rev-parse")
+                .arg("--verify")
+                .arg("--quiet")
+                .arg("HEAD")
+                .status()?,
+            GitVersion::Latest if self.shallow => git(&source_directory)
+                .arg("checkout")
+                .arg("--detach")
+                .arg("FETCH_HEAD")
+                .status()?,
+            GitVersion::Latest => {
+                self.attach_to_default_branch(&source_directory)?;
+                git(&source_directory)
+                    .env("GIT_MERGE_AUTOEDIT", "no")
+                    .arg("pull")
+                    .arg("--ff-only")
+                    .status()?
+            }
         };
 
         if !status.success() {
+            if let GitVersion::Latest = self.version {
+                if !self.shallow && self.has_local_commits(&source_directory) {
+                    return Err(self.local_commits_error(&source_directory));
+                }
+            }
+            if !modified_files.is_empty() {
+                return Err(Box::new(
+                    UserFacingError::new("Failed to build project")
+                        .reason(format!(
+                            "Could not checkout {:?} of {:?} over local modifications: {}",
+                            &self.version,
+                            &self.repository,
+                            modified_files.join(", ")
+                        ))
+                        .help("Commit, stash or revert the changes before building"),
+                ));
+            }
+            if offline {
+                return Err(Box::new(
+                    UserFacingError::new("Failed to build project")
+                        .reason(format!(
+                            "{:?} of {} is not available offline",
+                            &self.version,
+                            &self.repository.display_url()
+                        ))
+                        .help("Build once with network access to fetch the revision"),
+                ));
+            }
+            return Err(self.checkout_error());
+        }
+
+        self.update_submodules(&source_directory, offline)
+    }
+
+    /// A detached checkout, for example of a locked revision, has no branch to pull,
+    /// so the default branch of the remote is checked out before pulling the latest version
+    fn attach_to_default_branch(&self, source_directory: &Path) -> Result<(), Box<dyn Error>> {
+        let is_attached = git(source_directory)
+            .arg("symbolic-ref")
+            .arg("--quiet")
+            .arg("HEAD")
+            .output()?
+            .status
+            .success();
+        if is_attached {
+            return Ok(());
+        }
+
+        // commits made on top of the detached checkout would be left behind
+        let is_behind = git(source_directory)
+            .arg("merge-base")
+            .arg("--is-ancestor")
+            .arg("HEAD")
+            .arg("refs/remotes/origin/HEAD")
+            .status()?
+            .success();
+        if !is_behind {
+            return Err(self.local_commits_error(source_directory));
+        }
+
+        let output = git(source_directory)
+            .arg("symbolic-ref")
+            .arg("--short")
+            .arg("refs/remotes/origin/HEAD")
+            .output()?;
+        let remote_branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let branch = remote_branch
+            .strip_prefix("origin/")
+            .filter(|_| output.status.success())
+            .ok_or_else(|| self.checkout_error())?;
+
+        let status = git(source_directory).arg("checkout").arg(branch).status()?;
+        if !status.success() {
+            return Err(self.checkout_error());
+        }
+        Ok(())
+    }
+
+    fn local_commits_error(&self, source_directory: &Path) -> Box<dyn Error> {
+        Box::new(
+            UserFacingError::new("Failed to build project")
+                .reason(format!(
+                    "{} has local commits that are not in {}",
+                    source_directory.display(),
+                    &self.repository
+                ))
+                .help("Rebase or remove the local commits, or pin a tag, commit or branch"),
+        )
+    }
+
+    /// Tracked files that differ from the checked out version.
+    /// A working tree that was never checked out has no local modifications,
+    /// even though git reports every tracked file as deleted.
+    fn modified_files(&self, source_directory: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+        if !self.is_checked_out(source_directory) {
+            return Ok(vec![]);
+        }
+        let output = git(source_directory)
+            .arg("status")
+            .arg("--porcelain")
+            .arg("--untracked-files=no")
+            .output()?;
+        if !output.status.success() {
+            return Ok(vec![]);
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.get(3..))
+            .map(|file| file.to_string())
+            .collect())
+    }
+
+    /// Return true if the working tree was checked out, a clone without checkout has no index yet
+    fn is_checked_out(&self, source_directory: &Path) -> bool {
+        git(source_directory)
+            .arg("rev-parse")
+            .arg("--git-path")
+            .arg("index")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .is_some_and(|output| {
+                source_directory
+                    .join(String::from_utf8_lossy(&output.stdout).trim())
+                    .exists()
+            })
+    }
+
+    /// Return true if the repository has a commit checked out, a shallow repository has none
+    /// until the requested version is fetched
+    fn has_head(&self, source_directory: &Path) -> bool {
+        git(source_directory)
+            .arg("rev-parse")
+            .arg("--verify")
+            .arg("--quiet")
+            .arg("HEAD")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+
+    /// Return true if the current branch has commits that are not in its upstream
+    fn has_local_commits(&self, source_directory: &Path) -> bool {
+        git(source_directory)
+            .arg("rev-list")
+            .arg("--count")
+            .arg("@{upstream}..HEAD")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+            })
+            .is_some_and(|count| count > 0)
+    }
+
+    fn update_submodules(
+        &self,
+        source_directory: &Path,
+        offline: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let (paths, recursive) = match &self.submodules {
+            GitSubmodules::None => return Ok(()),
+            GitSubmodules::All { recursive } => (vec![], *recursive),
+            GitSubmodules::Paths { paths, recursive } => (paths.clone(), *recursive),
+        };
+
+        // submodule urls may have changed between versions
+        let mut sync = git(source_directory);
+        sync.arg("submodule").arg("sync");
+        if recursive {
+            sync.arg("--recursive");
+        }
+        sync.arg("--").args(&paths);
+
+        let mut update = git(source_directory);
+        update
+            .arg("submodule")
+            .arg("update")
+            .arg("--init")
+            .arg("--force");
+        if recursive {
+            update.arg("--recursive");
+        }
+        if offline {
+            update.arg("--no-fetch");
+        } else if self.shallow {
+            update.arg("--depth").arg("1");
+        }
+        update.arg("--").args(&paths);
+
+        if !sync.status()?.success() || !update.status()?.success() {
             return Err(Box::new(
                 UserFacingError::new("Failed to build project")
                     .reason(format!(
-                        "Could not checkout {:?} of {:?}",
-                        &self.version, &self.repository
+                        "Could not update submodules {:?} of {:?}",
+                        &self.submodules, &self.repository
                     ))
-                    .help("Make sure the configuration is correct and the git repository exists"),
+                    .help("Make sure the submodules exist and their repositories are accessible"),
             ));
         }
+        Ok(())
+    }
+
+    /// Urls of the repository to clone and fetch from, in order, rewritten by the mirrors
+    fn remote_urls(&self, context: &LibraryCompilationContext) -> Vec<String> {
+        context.mirrored_urls(&self.repository.to_string())
+    }
+
+    fn clone_repository(
+        &self,
+        source_directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Result<(), Box<dyn Error>> {
+        let remote_urls = self.remote_urls(context);
+
+        // a shallow repository is created empty and the requested version is fetched later
+        if self.shallow {
+            std::fs::create_dir_all(source_directory)?;
+            let status = git(source_directory).arg("init").status()?;
+            if status.success()
+                && git(source_directory)
+                    .arg("remote")
+                    .arg("add")
+                    .arg("origin")
+                    .arg(&remote_urls[0])
+                    .status()?
+                    .success()
+            {
+                return Ok(());
+            }
+        } else {
+            for remote_url in &remote_urls {
+                let mut command = Command::new("git");
+                command.env("GIT_TERMINAL_PROMPT", "0").arg("clone");
+                if self.blobless {
+                    command.arg("--filter=blob:none");
+                }
+                if !self.sparse.is_empty() {
+                    command.arg("--no-checkout");
+                }
+                if command
+                    .arg(remote_url)
+                    .arg(source_directory)
+                    .status()?
+                    .success()
+                {
+                    return Ok(());
+                }
+                if source_directory.exists() {
+                    std::fs::remove_dir_all(source_directory)?;
+                }
+            }
+        }
+
+        let mut error = UserFacingError::new("Failed to build project").reason(format!(
+            "Could not clone {}",
+            &self.repository.display_url()
+        ));
+        if remote_urls.len() > 1 || remote_urls[0] != self.repository.to_string() {
+            error = error.reason(format!("tried mirrors: {}", remote_urls.join(", ")));
+        }
+        Err(Box::new(error.help(
+            "Make sure the configuration is correct and the git repository exists",
+        )))
+    }
+
+    /// Fetch from the first mirror of the repository that succeeds.
+    /// The origin remote is pointed to the mirror, so that existing clones follow the mirrors too.
+    fn fetch(
+        &self,
+        source_directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Result<(), Box<dyn Error>> {
+        let remote_urls = self.remote_urls(context);
+        let mut last_error = None;
+
+        for remote_url in &remote_urls {
+            git(source_directory)
+                .arg("remote")
+                .arg("set-url")
+                .arg("origin")
+                .arg(remote_url)
+                .status()?;
+
+            if self.shallow {
+                match self.fetch_shallow(source_directory) {
+                    Ok(()) => return Ok(()),
+                    Err(error) => last_error = Some(error),
+                }
+            } else if git(source_directory)
+                .arg("fetch")
+                .arg("--all")
+                .arg("--tags")
+                .status()?
+                .success()
+            {
+                return Ok(());
+            }
+            println!("Could not fetch {}", remote_url);
+        }
 
+        // a failed full fetch is not fatal, the requested version may already be available
+        if let Some(error) = last_error {
+            return Err(error);
+        }
         Ok(())
     }
 
+    fn fetch_shallow(&self, source_directory: &Path) -> Result<(), Box<dyn Error>> {
+        let refspec = match &self.version {
+            GitVersion::Tag(tag) => format!("+refs/tags/{}:refs/tags/{}", tag, tag),
+            GitVersion::Commit(commit) => commit.clone(),
+            GitVersion::Branch(branch) => {
+                format!("+refs/heads/{}:refs/remotes/origin/{}", branch, branch)
+            }
+            GitVersion::Latest => "HEAD".to_string(),
+        };
+
+        let mut fetch = git(source_directory);
+        fetch.arg("fetch").arg("--depth").arg("1");
+        if self.blobless {
+            fetch.arg("--filter=blob:none");
+        }
+        if fetch.arg("origin").arg(&refspec).status()?.success() {
+            return Ok(());
+        }
+
+        // not every server allows to fetch an arbitrary commit, fall back to the full history
+        println!(
+            "Could not fetch {} of {} directly, fetching the full history",
+            &refspec, &self.repository
+        );
+        let mut fetch = git(source_directory);
+        fetch.arg("fetch").arg("--tags");
+        if source_directory.join(".git").join("shallow").exists() {
+            fetch.arg("--unshallow");
+        }
+        if self.blobless {
+            fetch.arg("--filter=blob:none");
+        }
+        if !fetch.arg("origin").status()?.success() {
+            return Err(self.checkout_error());
+        }
+        Ok(())
+    }
+
+    fn checkout_error(&self) -> Box<dyn Error> {
+        Box::new(
+            UserFacingError::new("Failed to build project")
+                .reason(format!(
+                    "Could not checkout {:?} of {:?}",
+                    &self.version, &self.repository
+                ))
+                .help("Make sure the configuration is correct and the git repository exists"),
+        )
+    }
+
     #[cfg(not(feature = "downloader"))]
     pub(crate) fn retrieve_prebuilt_library(
         &self,
@@ -253,6 +1007,25 @@ impl GitLocation {
             _ => None,
         }
     }
+
+    /// Unpack a prebuilt bundle from the GitHub release of the tag into the native library prefix
+    #[cfg(feature = "tar-location")]
+    pub(crate) fn retrieve_prebuilt_bundle(
+        &self,
+        library: &dyn Library,
+        context: &LibraryCompilationContext,
+    ) -> Option<PathBuf> {
+        match &self.repository {
+            GitRepository::GitHub(owner, repo) => github_downloader::retrieve_prebuilt_bundle(
+                owner,
+                repo,
+                &self.version,
+                library,
+                context,
+            ),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(feature = "downloader")]
@@ -262,14 +1035,14 @@ mod github_downloader {
     use std::error::Error;
     use std::path::{Path, PathBuf};
 
-    use downloader::{Download, Downloader};
     use feenk_download_auth_client::{
         download_release_asset_with_env_auth, EnvDownloadRequest, InstallationTokenSource,
     };
     use user_error::UserFacingError;
 
     use super::GitVersion;
-    use crate::{Library, LibraryCompilationContext};
+    use crate::components::{fetch_prebuilt_asset, stage_prebuilt_asset, verify_prebuilt_asset};
+    use crate::{Library, LibraryCompilationContext, LibraryOrigin};
 
     pub(super) fn retrieve_prebuilt_library(
         owner: &str,
@@ -285,7 +1058,12 @@ mod github_downloader {
                 let binary_path = library.exported_library_path(context);
                 let build_directory = binary_path.parent().unwrap();
 
-                if binary_path.exists() {
+                let asset_name = library.prebuilt_library_asset_name(context);
+                let origin = LibraryOrigin::prebuilt(
+                    release_asset_url(owner, repo, tag, &asset_name),
+                    &asset_name,
+                );
+                if origin.is_current(&binary_path) {
                     println!("{} already exists.", binary_path.display());
                     return Some(binary_path);
                 }
@@ -294,44 +1072,27 @@ mod github_downloader {
                     std::fs::create_dir_all(build_directory).unwrap();
                 }
 
-                let asset_name = library.prebuilt_library_asset_name(context);
-
-                match installation_token_source(library.name()) {
-                    Ok(Some(token_source)) => match download_private_release_asset(
+                match stage_prebuilt_asset(&binary_path, |directory| {
+                    fetch_release_asset(
                         owner,
                         repo,
                         tag,
                         &asset_name,
-                        &binary_path,
-                        token_source,
-                    ) {
-                        Ok(()) => Some(binary_path),
-                        Err(error) => {
-                            fail_prebuilt_library_retrieval(
-                                format!(
-                                    "Failed to download private GitHub release asset {} from {}/{}@{}",
-                                    asset_name, owner, repo, tag
-                                ),
-                                error,
-                            );
-                        }
-                    },
-                    Ok(None) => download_public_release_asset(
-                        owner,
-                        repo,
-                        tag,
-                        &asset_name,
-                        build_directory,
-                        &binary_path,
-                    ),
+                        library.name(),
+                        directory,
+                        context,
+                    )
+                }) {
+                    Ok(()) => {
+                        origin.record(&binary_path).ok()?;
+                        Some(binary_path)
+                    }
                     Err(error) => {
-                        fail_prebuilt_library_retrieval(
-                            format!(
-                                "Failed to read GitHub authentication configuration for {}",
-                                library.name()
-                            ),
-                            error
+                        eprintln!(
+                            "Failed to retrieve {} from {}/{}@{} due to {}",
+                            asset_name, owner, repo, tag, error
                         );
+                        None
                     }
                 }
             }
@@ -339,44 +1100,137 @@ mod github_downloader {
         }
     }
 
-    fn download_public_release_asset(
+    #[cfg(feature = "tar-location")]
+    pub(super) fn retrieve_prebuilt_bundle(
         owner: &str,
         repo: &str,
-        tag: &str,
-        asset_name: &str,
-        build_directory: &Path,
-        binary_path: &Path,
+        version: &GitVersion,
+        library: &dyn Library,
+        context: &LibraryCompilationContext,
     ) -> Option<PathBuf> {
-        let mut downloader = Downloader::builder()
-            .download_folder(build_directory)
-            .build()
-            .unwrap();
+        let tag = match version {
+            GitVersion::Tag(tag) => tag,
+            _ => return None,
+        };
+
+        let asset_name = library.prebuilt_bundle_asset_name(context);
+        let url = release_asset_url(owner, repo, tag, &asset_name);
 
-        let url =
-            format!("https://github.com/{owner}/{repo}/releases/download/{tag}/{asset_name}");
+        crate::components::retrieve_prebuilt_bundle(library, &url, context, |directory| {
+            fetch_release_asset(
+                owner,
+                repo,
+                tag,
+                &asset_name,
+                library.name(),
+                directory,
+                context,
+            )
+        })
+    }
+
+    fn release_asset_url(owner: &str, repo: &str, tag: &str, asset_name: &str) -> String {
+        format!("https://github.com/{owner}/{repo}/releases/download/{tag}/{asset_name}")
+    }
 
-        let to_download = Download::new(&url);
+    /// Download the release asset and its companion files into the directory and verify it.
+    /// Assets of private repositories are downloaded with the per-library GitHub authentication.
+    fn fetch_release_asset(
+        owner: &str,
+        repo: &str,
+        tag: &str,
+        asset_name: &str,
+        library_name: &str,
+        directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        let url = release_asset_url(owner, repo, tag, asset_name);
 
-        let mut result = match downloader.download(&[to_download]) {
-            Ok(result) => result,
+        let token_source = match installation_token_source(library_name) {
+            Ok(Some(token_source)) => token_source,
+            Ok(None) => return fetch_prebuilt_asset(&url, asset_name, None, directory, context),
             Err(error) => {
-                eprintln!("Failed to download {} due to {:?}", &url, error);
-                return None;
+                return Err(prebuilt_library_retrieval_error(
+                    format!(
+                        "Failed to read GitHub authentication configuration for {}",
+                        library_name
+                    ),
+                    error,
+                ))
             }
         };
-        let download_result = match result.remove(0) {
-            Ok(result) => result,
+
+        let asset = match fetch_private_release_asset(
+            owner,
+            repo,
+            tag,
+            asset_name,
+            token_source,
+            directory,
+            context,
+        ) {
+            Ok(Some(asset)) => asset,
+            Ok(None) => return Err(format!("Offline, not downloading {}", &url).into()),
             Err(error) => {
-                eprintln!("Failed to download {} due to {:?}", &url, error);
-                return None;
+                return Err(prebuilt_library_retrieval_error(
+                    format!(
+                        "Failed to download private GitHub release asset {} from {}/{}@{}",
+                        asset_name, owner, repo, tag
+                    ),
+                    error,
+                ))
             }
         };
 
-        let downloaded_file_name = download_result.file_name;
+        verify_prebuilt_asset(&asset, asset_name, None, context, |companion| {
+            let token_source = installation_token_source(library_name).ok()??;
+            fetch_private_release_asset(
+                owner,
+                repo,
+                tag,
+                companion,
+                token_source,
+                directory,
+                context,
+            )
+            .ok()?
+        })?;
+        Ok(asset)
+    }
+
+    /// Copy the private release asset from the download cache or download it into the directory.
+    /// Return None when offline and the asset is not cached.
+    fn fetch_private_release_asset(
+        owner: &str,
+        repo: &str,
+        tag: &str,
+        asset_name: &str,
+        token_source: InstallationTokenSource,
+        directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Result<Option<PathBuf>, Box<dyn Error>> {
+        let url = release_asset_url(owner, repo, tag, asset_name);
+        let asset = directory.join(asset_name);
 
-        std::fs::rename(downloaded_file_name, binary_path).unwrap();
+        if let Some(cached) = context
+            .download_cache()
+            .and_then(|cache| cache.get(&url, None))
+        {
+            println!("Using cached download of {}", &url);
+            std::fs::copy(cached, &asset)?;
+            return Ok(Some(asset));
+        }
 
-        Some(binary_path.to_path_buf())
+        if context.is_offline() {
+            println!("Offline, not downloading {}", &url);
+            return Ok(None);
+        }
+
+        download_private_release_asset(owner, repo, tag, asset_name, &asset, token_source)?;
+        if let Some(cache) = context.download_cache() {
+            cache.insert(&url, None, &asset)?;
+        }
+        Ok(Some(asset))
     }
 
     fn download_private_release_asset(
@@ -408,8 +1262,7 @@ mod github_downloader {
 
         println!(
             "Downloaded release asset {} to {}",
-            asset.name,
-            output_display
+            asset.name, output_display
         );
 
         Ok(())
@@ -433,8 +1286,8 @@ mod github_downloader {
 
         let private_key = optional_env_value(&private_key_key)?;
         let customer_id = optional_env_value(&customer_id_key)?;
-        let auth_server_url = optional_env_value(&auth_server_url_key)?
-            .or(optional_env_value(&auth_server_key)?);
+        let auth_server_url =
+            optional_env_value(&auth_server_url_key)?.or(optional_env_value(&auth_server_key)?);
 
         if private_key.is_none() && customer_id.is_none() && auth_server_url.is_none() {
             return Ok(None);
@@ -497,14 +1350,13 @@ mod github_downloader {
             .help("Set either the per-library installation token, or the per-library customer id, private key, and auth server URL")
     }
 
-    fn fail_prebuilt_library_retrieval(reason: String, error: Box<dyn Error>) -> ! {
-        panic!(
-            "{}",
+    fn prebuilt_library_retrieval_error(reason: String, error: Box<dyn Error>) -> Box<dyn Error> {
+        Box::new(
             UserFacingError::new("Failed to retrieve prebuilt library")
                 .reason(format!("{reason}: {error:?}"))
                 .help(
                     "Fix the per-library GitHub authentication environment variables or the release asset configuration",
-                )
+                ),
         )
     }
 }
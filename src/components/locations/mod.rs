@@ -7,7 +7,7 @@ mod tar;
 mod zip;
 
 #[cfg(feature = "git-location")]
-pub use self::git::{GitLocation, GitRepository, GitVersion};
+pub use self::git::{GitCleanPolicy, GitLocation, GitRepository, GitSubmodules, GitVersion};
 pub use self::path::PathLocation;
 #[cfg(feature = "tar-location")]
 pub use self::tar::{TarArchive, TarUrlLocation};
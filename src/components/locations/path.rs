@@ -1,4 +1,4 @@
-use crate::{Library, LibraryCompilationContext};
+use crate::{Library, LibraryCompilationContext, LibraryPatch, PrebuiltUrlTemplate};
 use std::error::Error;
 use std::path::{Path, PathBuf};
 use user_error::UserFacingError;
@@ -10,6 +10,10 @@ pub struct PathLocation {
     path: PathBuf,
     #[serde(default)]
     prebuilt_library_directory: Option<PathBuf>,
+    #[serde(default)]
+    patches: Vec<LibraryPatch>,
+    #[serde(default)]
+    prebuilt_url: Option<PrebuiltUrlTemplate>,
 }
 
 impl PathLocation {
@@ -17,6 +21,8 @@ impl PathLocation {
         Self {
             path: path.into(),
             prebuilt_library_directory: None,
+            patches: vec![],
+            prebuilt_url: None,
         }
     }
 
@@ -26,18 +32,44 @@ impl PathLocation {
         location
     }
 
+    /// Apply a patch to a copy of the sources in the sources root, the original sources are never modified
+    pub fn patch(self, patch: LibraryPatch) -> Self {
+        let mut location = self;
+        location.patches.push(patch);
+        location
+    }
+
+    /// Download prebuilt libraries from the url instead of compiling them
+    pub fn prebuilt_url(self, prebuilt_url: PrebuiltUrlTemplate) -> Self {
+        let mut location = self;
+        location.prebuilt_url = Some(prebuilt_url);
+        location
+    }
+
+    pub(crate) fn patches(&self) -> &[LibraryPatch] {
+        &self.patches
+    }
+
+    pub(crate) fn prebuilt_url_template(&self) -> Option<&PrebuiltUrlTemplate> {
+        self.prebuilt_url.as_ref()
+    }
+
+    /// Sources with patches are copied into the sources root and patched there
     pub(crate) fn sources_directory(
         &self,
-        _default_source_directory: &Path,
-        _context: &LibraryCompilationContext,
+        default_source_directory: &Path,
+        context: &LibraryCompilationContext,
     ) -> PathBuf {
-        self.path.clone()
+        if self.patches.is_empty() {
+            return self.path.clone();
+        }
+        context.sources_root().join(default_source_directory)
     }
 
     pub(crate) fn ensure_sources(
         &self,
-        _default_source_directory: &Path,
-        _context: &LibraryCompilationContext,
+        default_source_directory: &Path,
+        context: &LibraryCompilationContext,
     ) -> Result<(), Box<dyn Error>> {
         if !self.path.exists() {
             return Err(Box::new(
@@ -49,6 +81,41 @@ impl PathLocation {
                     .help("Make sure the configuration is correct and the sources exist"),
             ));
         }
+
+        if !self.patches.is_empty() {
+            self.copy_sources(&self.sources_directory(default_source_directory, context))?;
+        }
+        Ok(())
+    }
+
+    /// Replace the copy with the current state of the sources, so that the patches are applied
+    /// to a copy that reflects the latest changes of the original sources
+    fn copy_sources(&self, copy: &Path) -> Result<(), Box<dyn Error>> {
+        let sources = self.path.canonicalize()?;
+        let resolved_copy = resolve_existing(copy);
+        if sources.starts_with(&resolved_copy) || resolved_copy.starts_with(&sources) {
+            return Err(Box::new(
+                UserFacingError::new("Failed to build project")
+                    .reason(format!(
+                        "Can not copy {} to patch it into {}",
+                        sources.display(),
+                        copy.display()
+                    ))
+                    .help("Move the sources directory out of the sources root"),
+            ));
+        }
+
+        if copy.exists() {
+            std::fs::remove_dir_all(copy)?;
+        }
+
+        std::fs::create_dir_all(copy)?;
+        let copy_options = fs_extra::dir::CopyOptions {
+            content_only: true,
+            overwrite: true,
+            ..Default::default()
+        };
+        fs_extra::dir::copy(&sources, copy, &copy_options)?;
         Ok(())
     }
 
@@ -64,12 +131,73 @@ impl PathLocation {
             std::fs::create_dir_all(exported_directory).ok()?;
         }
 
-        if prebuilt_library != exported_library {
-            std::fs::copy(prebuilt_library, &exported_library).ok()?;
+        let asset_name = library.prebuilt_library_asset_name(context);
+        let verified = if prebuilt_library == exported_library {
+            self.verify_prebuilt_asset(&prebuilt_library, &asset_name, context)
+        } else {
+            crate::components::stage_prebuilt_asset(&exported_library, |directory| {
+                let asset = directory.join(&asset_name);
+                std::fs::copy(&prebuilt_library, &asset)?;
+                self.verify_prebuilt_asset(&asset, &asset_name, context)?;
+                Ok(asset)
+            })
+        };
+
+        if let Err(error) = verified {
+            eprintln!(
+                "Failed to retrieve {} due to {}",
+                prebuilt_library.display(),
+                error
+            );
+            return None;
         }
+
+        crate::LibraryOrigin::prebuilt(prebuilt_library.display().to_string(), asset_name)
+            .record(&exported_library)
+            .ok()?;
         Some(exported_library)
     }
 
+    /// Unpack the prebuilt bundle from the prebuilt library directory into the native library prefix
+    #[cfg(feature = "tar-location")]
+    pub(crate) fn retrieve_prebuilt_bundle(
+        &self,
+        library: &dyn Library,
+        context: &LibraryCompilationContext,
+    ) -> Option<PathBuf> {
+        let directory = self.prebuilt_library_directory.as_ref()?;
+        let bundle = directory.join(library.prebuilt_bundle_asset_name(context));
+        if !bundle.is_file() {
+            return None;
+        }
+
+        // a bundle replaced in place is unpacked again
+        let origin = format!("{}#{}", bundle.display(), crate::sha256_file(&bundle).ok()?);
+        crate::components::retrieve_prebuilt_bundle(library, &origin, context, |_| {
+            self.verify_prebuilt_asset(
+                &bundle,
+                &library.prebuilt_bundle_asset_name(context),
+                context,
+            )?;
+            Ok(bundle)
+        })
+    }
+
+    /// Verify the prebuilt asset against its companion files in the prebuilt library directory
+    fn verify_prebuilt_asset(
+        &self,
+        asset: &Path,
+        asset_name: &str,
+        context: &LibraryCompilationContext,
+    ) -> Result<(), Box<dyn Error>> {
+        let directory = self.prebuilt_library_directory.as_ref();
+        crate::components::verify_prebuilt_asset(asset, asset_name, None, context, |companion| {
+            directory
+                .map(|directory| directory.join(companion))
+                .filter(|companion| companion.is_file())
+        })
+    }
+
     fn find_prebuilt_library(
         &self,
         library: &dyn Library,
@@ -82,3 +210,13 @@ impl PathLocation {
         prebuilt_library.is_file().then_some(prebuilt_library)
     }
 }
+
+/// Resolve the links of the part of the path that exists
+fn resolve_existing(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find_map(|ancestor| {
+            let resolved = ancestor.canonicalize().ok()?;
+            Some(resolved.join(path.strip_prefix(ancestor).ok()?))
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
@@ -1,50 +1,181 @@
-use crate::LibraryCompilationContext;
-use downloader::{Download, Downloader};
+use crate::components::{
+    download_file, ensure_checksum, unpack_atomically, unpack_tar, EntryPaths, StripComponents,
+};
+use crate::{LibraryCompilationContext, LibraryPatch, PrebuiltUrlTemplate};
 use std::error::Error;
 use std::fs::File;
+use std::io::Read;
 use std::path::{Path, PathBuf};
-use tar::Archive;
+use user_error::UserFacingError;
 
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TarUrlLocation {
     url: String,
-    archive: TarArchive,
+    /// detected from the url or the content of the archive when not specified
+    #[serde(default)]
+    archive: Option<TarArchive>,
     sources: Option<PathBuf>,
+    #[serde(default)]
+    strip_components: StripComponents,
+    #[serde(default)]
+    patches: Vec<LibraryPatch>,
+    #[serde(default)]
+    prebuilt_url: Option<PrebuiltUrlTemplate>,
+    #[serde(default)]
+    sha256: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum TarArchive {
     Gz,
     Xz,
+    Bz2,
+    Zst,
+    /// An uncompressed tarball
+    None,
+}
+
+impl TarArchive {
+    /// Detect the compression from the extension of the url, for example `.tar.bz2` or `.tgz`
+    pub fn from_url(url: &str) -> Option<Self> {
+        let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+
+        [
+            (".tar.gz", TarArchive::Gz),
+            (".tgz", TarArchive::Gz),
+            (".tar.xz", TarArchive::Xz),
+            (".txz", TarArchive::Xz),
+            (".tar.bz2", TarArchive::Bz2),
+            (".tbz2", TarArchive::Bz2),
+            (".tbz", TarArchive::Bz2),
+            (".tar.zst", TarArchive::Zst),
+            (".tzst", TarArchive::Zst),
+            (".tar", TarArchive::None),
+        ]
+        .into_iter()
+        .find(|(extension, _)| path.ends_with(extension))
+        .map(|(_, archive)| archive)
+    }
+
+    /// Detect the compression from the magic bytes at the beginning of the file
+    pub fn from_content(path: impl AsRef<Path>) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut header = Vec::with_capacity(512);
+        File::open(path.as_ref())?
+            .take(512)
+            .read_to_end(&mut header)?;
+
+        let archive = if header.starts_with(&[0x1f, 0x8b]) {
+            Some(TarArchive::Gz)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(TarArchive::Xz)
+        } else if header.starts_with(b"BZh") {
+            Some(TarArchive::Bz2)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(TarArchive::Zst)
+        } else if header.get(257..262) == Some(b"ustar".as_slice()) {
+            Some(TarArchive::None)
+        } else {
+            None
+        };
+        Ok(archive)
+    }
+
+    fn unpack(
+        &self,
+        tarball: &Path,
+        destination: &Path,
+        entry_paths: &EntryPaths,
+    ) -> Result<(), Box<dyn Error>> {
+        let open = || -> Result<Box<dyn Read>, Box<dyn Error>> {
+            let file = File::open(tarball)?;
+            Ok(match self {
+                TarArchive::Gz => Box::new(flate2::read::GzDecoder::new(file)),
+                TarArchive::Xz => Box::new(xz2::read::XzDecoder::new(file)),
+                TarArchive::Bz2 => Box::new(bzip2::read::BzDecoder::new(file)),
+                TarArchive::Zst => Box::new(zstd::stream::read::Decoder::new(file)?),
+                TarArchive::None => Box::new(file),
+            })
+        };
+        unpack_tar(open, destination, entry_paths)
+    }
 }
 
 impl TarUrlLocation {
     pub fn new(url: impl Into<String>) -> Self {
         Self {
             url: url.into(),
-            archive: TarArchive::Gz,
+            archive: None,
             sources: None,
+            strip_components: StripComponents::None,
+            patches: vec![],
+            prebuilt_url: None,
+            sha256: None,
         }
     }
 
     pub fn archive(self, archive: TarArchive) -> Self {
         Self {
-            url: self.url,
-            archive,
-            sources: self.sources,
+            archive: Some(archive),
+            ..self
         }
     }
 
     pub fn sources(self, folder: impl Into<PathBuf>) -> Self {
         Self {
-            url: self.url,
-            archive: self.archive,
             sources: Some(folder.into()),
+            ..self
+        }
+    }
+
+    /// Remove the given amount of leading directories from the paths of the unpacked entries
+    pub fn strip_components(self, count: usize) -> Self {
+        Self {
+            strip_components: StripComponents::Count(count),
+            ..self
+        }
+    }
+
+    /// Flatten the top-level directory of the archive, for example `zlib-1.3.1/`,
+    /// when all entries are inside of it
+    pub fn auto_strip_components(self) -> Self {
+        Self {
+            strip_components: StripComponents::Auto,
+            ..self
+        }
+    }
+
+    /// Apply a patch to the sources after they are unpacked
+    pub fn patch(mut self, patch: LibraryPatch) -> Self {
+        self.patches.push(patch);
+        self
+    }
+
+    /// Verify the downloaded archive against the sha256 digest before unpacking
+    pub fn sha256(self, sha256: impl Into<String>) -> Self {
+        Self {
+            sha256: Some(sha256.into()),
+            ..self
+        }
+    }
+
+    /// Download prebuilt libraries from the url instead of compiling them
+    pub fn prebuilt_url(self, prebuilt_url: PrebuiltUrlTemplate) -> Self {
+        Self {
+            prebuilt_url: Some(prebuilt_url),
+            ..self
         }
     }
 
+    pub(crate) fn patches(&self) -> &[LibraryPatch] {
+        &self.patches
+    }
+
+    pub(crate) fn prebuilt_url_template(&self) -> Option<&PrebuiltUrlTemplate> {
+        self.prebuilt_url.as_ref()
+    }
+
     pub(crate) fn sources_directory(
         &self,
         default_source_directory: &Path,
@@ -53,6 +184,20 @@ impl TarUrlLocation {
         context.sources_root().join(default_source_directory)
     }
 
+    /// Return the url if the sources are neither unpacked nor cached
+    pub(crate) fn unavailable_offline(
+        &self,
+        default_source_directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Option<String> {
+        let source_directory = self.sources_directory(default_source_directory, context);
+        let cached = context
+            .download_cache()
+            .and_then(|cache| cache.get(&self.url, self.sha256.as_deref()));
+
+        (!source_directory.exists() && cached.is_none()).then(|| self.url.clone())
+    }
+
     pub(crate) fn ensure_sources(
         &self,
         default_source_directory: &Path,
@@ -61,49 +206,33 @@ impl TarUrlLocation {
         let source_directory = context.sources_root().join(default_source_directory);
 
         if !source_directory.exists() {
-            std::fs::create_dir_all(&source_directory)?;
-
-            let mut downloader = Downloader::builder()
-                .download_folder(&source_directory)
-                .build()?;
-
-            let to_download = Download::new(&self.url);
-
-            let mut result = downloader.download(&[to_download])?;
-            let download_result = result.remove(0)?;
-            let downloaded_path = download_result.file_name;
-
-            let downloaded_tar = File::open(&downloaded_path)?;
-
-            match self.archive {
-                TarArchive::Gz => {
-                    let xz = flate2::read::GzDecoder::new(downloaded_tar);
-                    let mut archive = Archive::new(xz);
-                    archive.unpack(&source_directory)?;
-                }
-                TarArchive::Xz => {
-                    let xz = xz2::read::XzDecoder::new(downloaded_tar);
-                    let mut archive = Archive::new(xz);
-                    archive.unpack(&source_directory)?;
-                }
+            ensure_checksum(self.sha256.as_ref(), &self.url, context)?;
+            if let Some(parent) = source_directory.parent() {
+                std::fs::create_dir_all(parent)?;
             }
 
-            std::fs::remove_file(&downloaded_path)?;
-
-            if let Some(ref sources) = self.sources {
-                let copy_options = fs_extra::dir::CopyOptions {
-                    content_only: true,
-                    ..Default::default()
-                };
-
-                fs_extra::dir::copy(
-                    source_directory.join(sources),
-                    &source_directory,
-                    &copy_options,
+            // the sources appear only once they are completely unpacked
+            let entry_paths = EntryPaths::new(self.sources.as_deref(), self.strip_components);
+            unpack_atomically(&source_directory, |download_directory, unpacked| {
+                let downloaded_path = download_file(
+                    &self.url,
+                    self.sha256.as_deref(),
+                    download_directory,
+                    context,
                 )?;
-
-                std::fs::remove_dir_all(source_directory.join(sources))?;
-            }
+                let archive = match self.archive.or_else(|| TarArchive::from_url(&self.url)) {
+                    Some(archive) => archive,
+                    None => TarArchive::from_content(&downloaded_path)?.ok_or_else(|| {
+                        UserFacingError::new("Failed to build project")
+                            .reason(format!(
+                                "Could not detect the archive type of {}",
+                                &self.url
+                            ))
+                            .help("Specify the archive type of the tar location")
+                    })?,
+                };
+                archive.unpack(&downloaded_path, unpacked, &entry_paths)
+            })?;
         }
         Ok(())
     }
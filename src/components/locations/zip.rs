@@ -1,7 +1,8 @@
-use crate::LibraryCompilationContext;
-use downloader::{Download, Downloader};
+use crate::components::{
+    download_file, ensure_checksum, extract_zip, unpack_atomically, EntryPaths, StripComponents,
+};
+use crate::{LibraryCompilationContext, LibraryPatch, PrebuiltUrlTemplate};
 use std::error::Error;
-use std::fs::File;
 use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
@@ -10,6 +11,14 @@ use serde::{Deserialize, Serialize};
 pub struct ZipUrlLocation {
     url: String,
     sources: Option<PathBuf>,
+    #[serde(default)]
+    strip_components: StripComponents,
+    #[serde(default)]
+    patches: Vec<LibraryPatch>,
+    #[serde(default)]
+    prebuilt_url: Option<PrebuiltUrlTemplate>,
+    #[serde(default)]
+    sha256: Option<String>,
 }
 
 impl ZipUrlLocation {
@@ -17,16 +26,67 @@ impl ZipUrlLocation {
         Self {
             url: url.into(),
             sources: None,
+            strip_components: StripComponents::None,
+            patches: vec![],
+            prebuilt_url: None,
+            sha256: None,
         }
     }
 
     pub fn sources(self, folder: impl Into<PathBuf>) -> Self {
         Self {
-            url: self.url,
             sources: Some(folder.into()),
+            ..self
         }
     }
 
+    /// Remove the given amount of leading directories from the paths of the unpacked entries
+    pub fn strip_components(self, count: usize) -> Self {
+        Self {
+            strip_components: StripComponents::Count(count),
+            ..self
+        }
+    }
+
+    /// Flatten the top-level directory of the archive, for example `zlib-1.3.1/`,
+    /// when all entries are inside of it
+    pub fn auto_strip_components(self) -> Self {
+        Self {
+            strip_components: StripComponents::Auto,
+            ..self
+        }
+    }
+
+    /// Apply a patch to the sources after they are unpacked
+    pub fn patch(mut self, patch: LibraryPatch) -> Self {
+        self.patches.push(patch);
+        self
+    }
+
+    /// Verify the downloaded archive against the sha256 digest before unpacking
+    pub fn sha256(self, sha256: impl Into<String>) -> Self {
+        Self {
+            sha256: Some(sha256.into()),
+            ..self
+        }
+    }
+
+    /// Download prebuilt libraries from the url instead of compiling them
+    pub fn prebuilt_url(self, prebuilt_url: PrebuiltUrlTemplate) -> Self {
+        Self {
+            prebuilt_url: Some(prebuilt_url),
+            ..self
+        }
+    }
+
+    pub(crate) fn patches(&self) -> &[LibraryPatch] {
+        &self.patches
+    }
+
+    pub(crate) fn prebuilt_url_template(&self) -> Option<&PrebuiltUrlTemplate> {
+        self.prebuilt_url.as_ref()
+    }
+
     pub(crate) fn sources_directory(
         &self,
         default_source_directory: &Path,
@@ -35,6 +95,20 @@ impl ZipUrlLocation {
         context.sources_root().join(default_source_directory)
     }
 
+    /// Return the url if the sources are neither unpacked nor cached
+    pub(crate) fn unavailable_offline(
+        &self,
+        default_source_directory: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Option<String> {
+        let source_directory = self.sources_directory(default_source_directory, context);
+        let cached = context
+            .download_cache()
+            .and_then(|cache| cache.get(&self.url, self.sha256.as_deref()));
+
+        (!source_directory.exists() && cached.is_none()).then(|| self.url.clone())
+    }
+
     pub(crate) fn ensure_sources(
         &self,
         default_source_directory: &Path,
@@ -43,38 +117,22 @@ impl ZipUrlLocation {
         let source_directory = context.sources_root().join(default_source_directory);
 
         if !source_directory.exists() {
-            std::fs::create_dir_all(&source_directory)?;
-
-            let mut downloader = Downloader::builder()
-                .download_folder(&source_directory)
-                .build()?;
-
-            let to_download = Download::new(&self.url);
-
-            let mut result = downloader.download(&[to_download])?;
-            let download_result = result.remove(0)?;
-            let downloaded_path = download_result.file_name;
-
-            let downloaded_zip = File::open(&downloaded_path)?;
-            let mut archive = zip::ZipArchive::new(downloaded_zip)?;
-            archive.extract(&source_directory)?;
-
-            std::fs::remove_file(&downloaded_path)?;
-
-            if let Some(ref sources) = self.sources {
-                let copy_options = fs_extra::dir::CopyOptions {
-                    content_only: true,
-                    ..Default::default()
-                };
+            ensure_checksum(self.sha256.as_ref(), &self.url, context)?;
+            if let Some(parent) = source_directory.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
 
-                fs_extra::dir::copy(
-                    source_directory.join(sources),
-                    &source_directory,
-                    &copy_options,
+            // the sources appear only once they are completely unpacked
+            let entry_paths = EntryPaths::new(self.sources.as_deref(), self.strip_components);
+            unpack_atomically(&source_directory, |download_directory, unpacked| {
+                let downloaded_path = download_file(
+                    &self.url,
+                    self.sha256.as_deref(),
+                    download_directory,
+                    context,
                 )?;
-
-                std::fs::remove_dir_all(source_directory.join(sources))?;
-            }
+                extract_zip(&downloaded_path, unpacked, &entry_paths)
+            })?;
         }
         Ok(())
     }
@@ -0,0 +1,83 @@
+use crate::LibraryCompilationContext;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+pub const LOCKFILE_NAME: &str = "libraries.lock";
+
+/// How resolved revisions of sources are recorded in and read from the lockfile
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum LockMode {
+    /// Check out the requested versions and record revisions of libraries that are not locked yet.
+    /// Branches and latest versions that are already locked are checked out at their locked revision.
+    #[default]
+    Record,
+    /// Check out exactly the revisions from the lockfile and fail if a library is not locked
+    Locked,
+    /// Check out the requested versions and replace the recorded revisions
+    Update,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedRevision {
+    pub repository: String,
+    /// The requested version the revision is resolved from, for example a branch
+    pub version: String,
+    pub revision: String,
+}
+
+/// Resolved revisions of libraries keyed by their sources directory, stored as toml
+/// in the sources root
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(default, rename = "library")]
+    libraries: BTreeMap<String, LockedRevision>,
+}
+
+impl Lockfile {
+    pub fn path(context: &LibraryCompilationContext) -> PathBuf {
+        context.sources_root().join(LOCKFILE_NAME)
+    }
+
+    /// Read a lockfile, a missing file is an empty lockfile
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn revision(&self, name: &str) -> Option<&LockedRevision> {
+        self.libraries.get(name)
+    }
+
+    pub fn record(
+        &mut self,
+        name: impl Into<String>,
+        repository: impl Into<String>,
+        version: impl Into<String>,
+        revision: impl Into<String>,
+    ) {
+        self.libraries.insert(
+            name.into(),
+            LockedRevision {
+                repository: repository.into(),
+                version: version.into(),
+                revision: revision.into(),
+            },
+        );
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.libraries.is_empty()
+    }
+}
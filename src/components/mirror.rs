@@ -0,0 +1,83 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Rewrites urls that start with a prefix to the same path on one or more mirrors.
+/// The mirrors are tried in order until one of them succeeds.
+///
+/// For example `https://github.com/=https://mirror.local/github/` rewrites
+/// `https://github.com/feenkcom/gtoolkit.git` to `https://mirror.local/github/feenkcom/gtoolkit.git`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlMirror {
+    prefix: String,
+    mirrors: Vec<String>,
+}
+
+impl UrlMirror {
+    pub fn new(prefix: impl Into<String>, mirror: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            mirrors: vec![mirror.into()],
+        }
+    }
+
+    /// Add a mirror that is tried when the previous ones fail
+    pub fn fallback(mut self, mirror: impl Into<String>) -> Self {
+        self.mirrors.push(mirror.into());
+        self
+    }
+
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_str()
+    }
+
+    pub fn mirrors(&self) -> &[String] {
+        self.mirrors.as_slice()
+    }
+
+    pub fn matches(&self, url: &str) -> bool {
+        url.starts_with(&self.prefix)
+    }
+
+    /// Return the url rewritten to every mirror, in order, or None if the prefix does not match
+    pub fn rewrite(&self, url: &str) -> Option<Vec<String>> {
+        let path = url.strip_prefix(&self.prefix)?;
+        Some(
+            self.mirrors
+                .iter()
+                .map(|mirror| format!("{}{}", mirror, path))
+                .collect(),
+        )
+    }
+}
+
+/// Parse a rule in the form of `prefix=mirror[,fallback...]`
+impl FromStr for UrlMirror {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let (prefix, mirrors) = rule
+            .split_once('=')
+            .ok_or_else(|| format!("{} is not in the form of prefix=mirror", rule))?;
+
+        let mirrors = mirrors
+            .split(',')
+            .map(|mirror| mirror.trim().to_string())
+            .filter(|mirror| !mirror.is_empty())
+            .collect::<Vec<String>>();
+
+        if prefix.trim().is_empty() || mirrors.is_empty() {
+            return Err(format!("{} is not in the form of prefix=mirror", rule));
+        }
+
+        Ok(Self {
+            prefix: prefix.trim().to_string(),
+            mirrors,
+        })
+    }
+}
+
+impl Display for UrlMirror {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", &self.prefix, self.mirrors.join(","))
+    }
+}
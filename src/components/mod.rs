@@ -1,13 +1,63 @@
+mod checksum;
 mod context;
 mod dependencies;
+mod download_cache;
+#[cfg(any(feature = "tar-location", feature = "zip-location"))]
+mod extract;
+mod library_origin;
 mod location;
+mod lockfile;
 mod locations;
+mod mirror;
 mod options;
+mod patch;
+mod prebuilt_bundle;
+mod prebuilt_url;
+mod prebuilt_verification;
+mod publish;
 mod target;
 
+#[cfg(any(feature = "tar-location", feature = "zip-location"))]
+pub(crate) use checksum::ensure_checksum;
+#[cfg(feature = "downloader")]
+pub(crate) use checksum::verify_sha256;
+pub use checksum::sha256_file;
 pub use context::LibraryCompilationContext;
 pub use dependencies::LibraryDependencies;
+#[cfg(feature = "downloader")]
+pub(crate) use download_cache::download_file;
+#[cfg(feature = "zip-location")]
+pub(crate) use extract::extract_zip;
+#[cfg(any(feature = "tar-location", feature = "zip-location"))]
+pub(crate) use extract::{unpack_atomically, EntryPaths};
+#[cfg(any(feature = "tar-location", feature = "zip-location"))]
+pub use extract::StripComponents;
+#[cfg(feature = "tar-location")]
+pub(crate) use extract::unpack_tar;
+pub use download_cache::{CachedDownload, DownloadCache, DOWNLOAD_CACHE_ENV_VAR};
+pub use library_origin::{LibraryOrigin, LIBRARY_ORIGIN_EXTENSION};
 pub use location::LibraryLocation;
+pub use lockfile::{LockMode, LockedRevision, Lockfile, LOCKFILE_NAME};
 pub use locations::*;
+pub use mirror::UrlMirror;
 pub use options::LibraryOptions;
+pub(crate) use patch::apply_patches;
+#[cfg(feature = "git-location")]
+pub(crate) use patch::revert_patches;
+pub use patch::{LibraryPatch, APPLIED_PATCHES_DIRECTORY};
+#[cfg(feature = "tar-location")]
+pub(crate) use prebuilt_bundle::retrieve_prebuilt_bundle;
+#[cfg(feature = "tar-location")]
+pub use prebuilt_bundle::create_prebuilt_bundle;
+pub use prebuilt_bundle::{prebuilt_bundle_origin, PREBUILT_BUNDLE_MARKER};
+pub use prebuilt_url::PrebuiltUrlTemplate;
+#[cfg(feature = "downloader")]
+pub(crate) use prebuilt_verification::fetch_prebuilt_asset;
+pub use prebuilt_verification::signature_asset_name;
+pub(crate) use prebuilt_verification::{stage_prebuilt_asset, verify_prebuilt_asset};
+#[cfg(feature = "downloader")]
+pub use publish::GitHubReleasePublisher;
+pub use publish::{
+    checksum_asset_name, publish_prebuilt_library, DirectoryPublisher, PrebuiltPublisher,
+};
 pub use target::LibraryTarget;
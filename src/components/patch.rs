@@ -0,0 +1,203 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use user_error::UserFacingError;
+
+/// A directory inside of the sources with copies of the applied patches
+pub const APPLIED_PATCHES_DIRECTORY: &str = ".applied-patches";
+
+/// A unified diff applied to the sources of a library after they are fetched.
+/// Paths in the diff are relative to the sources directory and have `a/` and `b/` prefixes,
+/// as created by `git diff` or `diff -ru a b`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LibraryPatch {
+    File(PathBuf),
+    Inline { name: String, contents: String },
+}
+
+impl LibraryPatch {
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self::File(path.into())
+    }
+
+    pub fn inline(name: impl Into<String>, contents: impl Into<String>) -> Self {
+        Self::Inline {
+            name: name.into(),
+            contents: contents.into(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            LibraryPatch::File(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string()),
+            LibraryPatch::Inline { name, .. } => name.clone(),
+        }
+    }
+
+    pub fn contents(&self) -> Result<String, Box<dyn Error>> {
+        match self {
+            LibraryPatch::File(path) => std::fs::read_to_string(path).map_err(|error| {
+                UserFacingError::new("Failed to build project")
+                    .reason(format!(
+                        "Could not read patch {}: {}",
+                        path.display(),
+                        error
+                    ))
+                    .help("Make sure the patch file exists")
+                    .into()
+            }),
+            LibraryPatch::Inline { contents, .. } => Ok(contents.clone()),
+        }
+    }
+
+    /// A sha256 digest of the name and contents of the patch
+    pub fn fingerprint(&self) -> Result<String, Box<dyn Error>> {
+        let mut hasher = Sha256::new();
+        hasher.update(self.name().as_bytes());
+        hasher.update(self.contents()?.as_bytes());
+        Ok(hex::encode(hasher.finalize()))
+    }
+}
+
+/// Apply the patches in order, skipping the ones that are already applied.
+/// When previously applied patches differ from the requested ones, they are reverted first.
+pub(crate) fn apply_patches(
+    patches: &[LibraryPatch],
+    source_directory: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let applied = applied_patches(source_directory)?;
+
+    let mut requested = vec![];
+    for (index, patch) in patches.iter().enumerate() {
+        requested.push(applied_patch_file_name(index, patch)?);
+    }
+
+    if !requested.starts_with(&applied) {
+        revert_patches(source_directory)?;
+        return apply_patches(patches, source_directory);
+    }
+
+    for (index, patch) in patches.iter().enumerate().skip(applied.len()) {
+        let contents = patch.contents()?;
+        let applied_directory = source_directory.join(APPLIED_PATCHES_DIRECTORY);
+        std::fs::create_dir_all(&applied_directory)?;
+        let applied_patch = applied_directory.join(&requested[index]);
+        std::fs::write(&applied_patch, &contents)?;
+
+        let output = git_apply(source_directory, &applied_patch, &["--check"])?;
+        if !output.status.success() {
+            // the sources may already contain the changes, for example when the marker was lost
+            if git_apply(source_directory, &applied_patch, &["--reverse", "--check"])?
+                .status
+                .success()
+            {
+                println!("Patch {} is already applied", patch.name());
+                continue;
+            }
+            std::fs::remove_file(&applied_patch)?;
+            return Err(patch_error("apply", patch, source_directory, &output));
+        }
+
+        let output = git_apply(source_directory, &applied_patch, &[])?;
+        if !output.status.success() {
+            std::fs::remove_file(&applied_patch)?;
+            return Err(patch_error("apply", patch, source_directory, &output));
+        }
+        println!(
+            "Applied patch {} to {}",
+            patch.name(),
+            source_directory.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Revert all applied patches in the reverse order
+pub(crate) fn revert_patches(source_directory: &Path) -> Result<(), Box<dyn Error>> {
+    let applied_directory = source_directory.join(APPLIED_PATCHES_DIRECTORY);
+
+    for applied_patch in applied_patches(source_directory)?.iter().rev() {
+        let patch_file = applied_directory.join(applied_patch);
+        let output = git_apply(source_directory, &patch_file, &["--reverse"])?;
+        if !output.status.success() {
+            let patch = LibraryPatch::file(&patch_file);
+            return Err(patch_error("revert", &patch, source_directory, &output));
+        }
+        std::fs::remove_file(&patch_file)?;
+    }
+
+    if applied_directory.exists() {
+        std::fs::remove_dir_all(&applied_directory)?;
+    }
+    Ok(())
+}
+
+/// File names of the applied patches in the order they were applied
+fn applied_patches(source_directory: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let applied_directory = source_directory.join(APPLIED_PATCHES_DIRECTORY);
+    if !applied_directory.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut applied = vec![];
+    for entry in applied_directory.read_dir()? {
+        applied.push(entry?.file_name().to_string_lossy().to_string());
+    }
+    applied.sort();
+    Ok(applied)
+}
+
+fn applied_patch_file_name(index: usize, patch: &LibraryPatch) -> Result<String, Box<dyn Error>> {
+    let fingerprint = patch.fingerprint()?;
+    Ok(format!("{:04}-{}.patch", index + 1, &fingerprint[..16]))
+}
+
+fn git_apply(
+    source_directory: &Path,
+    patch_file: &Path,
+    args: &[&str],
+) -> Result<Output, Box<dyn Error>> {
+    let mut command = Command::new("git");
+    command.current_dir(source_directory).arg("apply");
+    // do not let git treat the sources as part of an enclosing repository
+    if let Some(parent) = source_directory.parent() {
+        command.env("GIT_CEILING_DIRECTORIES", parent);
+    }
+    command
+        .arg("--whitespace=nowarn")
+        .args(args)
+        .arg(patch_file)
+        .output()
+        .map_err(|error| {
+            UserFacingError::new("Failed to build project")
+                .reason(format!("Could not run git apply: {}", error))
+                .help("Make sure git is installed and is in the PATH")
+                .into()
+        })
+}
+
+fn patch_error(
+    action: &str,
+    patch: &LibraryPatch,
+    source_directory: &Path,
+    output: &Output,
+) -> Box<dyn Error> {
+    let mut error = UserFacingError::new("Failed to build project").reason(format!(
+        "Could not {} patch {} to {}",
+        action,
+        patch.name(),
+        source_directory.display()
+    ));
+    // git names the file and line of the failing hunk
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        error = error.reason(line.to_string());
+    }
+    Box::new(error.help("Make sure the patch matches the version of the sources"))
+}
@@ -0,0 +1,177 @@
+use std::path::Path;
+#[cfg(feature = "tar-location")]
+use std::{error::Error, fs::File, path::PathBuf};
+
+#[cfg(feature = "tar-location")]
+use crate::components::{unpack_atomically, unpack_tar, EntryPaths};
+#[cfg(feature = "tar-location")]
+use crate::{Library, LibraryCompilationContext, StripComponents};
+#[cfg(feature = "tar-location")]
+use user_error::UserFacingError;
+
+/// A file in the native library prefix that records where the unpacked prebuilt bundle comes from
+pub const PREBUILT_BUNDLE_MARKER: &str = ".prebuilt-bundle";
+
+/// Return where the prebuilt bundle unpacked into the prefix comes from,
+/// or None if the prefix is not unpacked from a bundle
+pub fn prebuilt_bundle_origin(prefix: &Path) -> Option<String> {
+    std::fs::read_to_string(prefix.join(PREBUILT_BUNDLE_MARKER))
+        .ok()
+        .map(|origin| origin.trim().to_string())
+}
+
+/// Pack the library, header and pkg-config directories of the native library prefix
+/// into a gzipped tarball in the build root, named after the bundle asset name of the library
+#[cfg(feature = "tar-location")]
+pub fn create_prebuilt_bundle(
+    library: &dyn Library,
+    context: &LibraryCompilationContext,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let prefix = bundle_prefix(library, context)?;
+
+    let mut directories = library.compiled_library_directories(context);
+    directories.extend(library.native_library_include_headers(context));
+    directories.extend(library.pkg_config_directory(context));
+
+    let directories = directories
+        .iter()
+        .filter(|directory| directory.is_dir())
+        .filter_map(|directory| directory.strip_prefix(&prefix).ok())
+        .filter(|directory| !directory.as_os_str().is_empty())
+        .collect::<Vec<&Path>>();
+
+    // nested directories, for example lib/pkgconfig, are packed together with their parent
+    let mut bundled = directories
+        .iter()
+        .filter(|directory| {
+            !directories
+                .iter()
+                .any(|other| other != *directory && directory.starts_with(other))
+        })
+        .collect::<Vec<&&Path>>();
+    bundled.sort();
+    bundled.dedup();
+
+    if bundled.is_empty() {
+        return Err(Box::new(
+            UserFacingError::new("Failed to create a prebuilt bundle")
+                .reason(format!(
+                    "{} has no libraries or headers in {}",
+                    library.name(),
+                    prefix.display()
+                ))
+                .help("Compile the library before bundling it"),
+        ));
+    }
+
+    let bundle = context
+        .build_root()
+        .join(library.prebuilt_bundle_asset_name(context));
+    let encoder =
+        flate2::write::GzEncoder::new(File::create(&bundle)?, flate2::Compression::default());
+
+    let mut archive = tar::Builder::new(encoder);
+    archive.follow_symlinks(false);
+    for directory in bundled {
+        archive.append_dir_all(directory, prefix.join(directory))?;
+    }
+    archive.into_inner()?.finish()?;
+
+    Ok(bundle)
+}
+
+/// Unpack the prebuilt bundle returned by `fetch` into the native library prefix,
+/// replacing its previous content, and return the prefix.
+/// `fetch` is given a temporary directory to download the bundle into.
+/// A bundle that is already unpacked from the same origin is reused.
+/// A failed retrieval is not an error, the library is compiled from sources instead.
+#[cfg(feature = "tar-location")]
+pub(crate) fn retrieve_prebuilt_bundle(
+    library: &dyn Library,
+    origin: &str,
+    context: &LibraryCompilationContext,
+    fetch: impl FnOnce(&Path) -> Result<PathBuf, Box<dyn Error>>,
+) -> Option<PathBuf> {
+    let prefix = library.native_library_prefix(context);
+    if prebuilt_bundle_origin(&prefix).as_deref() == Some(origin) {
+        println!(
+            "Prebuilt bundle of {} is already unpacked in {}",
+            library.name(),
+            prefix.display()
+        );
+        return Some(prefix);
+    }
+
+    match unpack_prebuilt_bundle(library, origin, context, fetch) {
+        Ok(()) => {
+            println!(
+                "Unpacked prebuilt bundle of {} from {}",
+                library.name(),
+                origin
+            );
+            Some(prefix)
+        }
+        Err(error) => {
+            eprintln!(
+                "Failed to retrieve a prebuilt bundle of {} from {} due to {}",
+                library.name(),
+                origin,
+                error
+            );
+            None
+        }
+    }
+}
+
+#[cfg(feature = "tar-location")]
+fn unpack_prebuilt_bundle(
+    library: &dyn Library,
+    origin: &str,
+    context: &LibraryCompilationContext,
+    fetch: impl FnOnce(&Path) -> Result<PathBuf, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let prefix = bundle_prefix(library, context)?;
+    if let Some(parent) = prefix.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    unpack_atomically(&prefix, |download_directory, unpacked| {
+        let bundle = fetch(download_directory)?;
+        unpack_tar(
+            || Ok(flate2::read::GzDecoder::new(File::open(&bundle)?)),
+            unpacked,
+            &EntryPaths::new(None, StripComponents::None),
+        )?;
+        std::fs::write(unpacked.join(PREBUILT_BUNDLE_MARKER), origin)?;
+
+        if prefix.exists() {
+            std::fs::remove_dir_all(&prefix)?;
+        }
+        Ok(())
+    })?;
+
+    library.relocate_pkg_config_files(context)
+}
+
+/// Return the native library prefix of the library, which is replaced when its bundle is unpacked.
+/// Libraries that install into the build root itself, for example rust libraries
+/// without cargo-c, share their prefix with everything else and can not be bundled.
+#[cfg(feature = "tar-location")]
+fn bundle_prefix(
+    library: &dyn Library,
+    context: &LibraryCompilationContext,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let prefix = library.native_library_prefix(context);
+    if context.build_root().starts_with(&prefix) {
+        return Err(Box::new(
+            UserFacingError::new("Failed to bundle prebuilt library")
+                .reason(format!(
+                    "{} is installed into the build root {}",
+                    library.name(),
+                    prefix.display()
+                ))
+                .help("Only libraries with their own prefix in the build root can be bundled"),
+        ));
+    }
+    Ok(prefix)
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Library, LibraryCompilationContext};
+
+/// A url of prebuilt libraries with placeholders, for example `https://host/{name}/{version}/{asset}`.
+/// `{name}` is replaced with the name of the library, `{version}` with the version of the prebuilt
+/// library or of the location, `{target}` with the target triple and `{asset}` with
+/// the asset name of the prebuilt library or bundle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrebuiltUrlTemplate {
+    template: String,
+    #[serde(default)]
+    version: Option<String>,
+    /// sha256 digests of the prebuilt libraries by their asset name
+    #[serde(default)]
+    sha256: BTreeMap<String, String>,
+}
+
+impl PrebuiltUrlTemplate {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+            version: None,
+            sha256: BTreeMap::new(),
+        }
+    }
+
+    /// The version of the prebuilt libraries, by default it is the tag or commit of a git location
+    pub fn version(self, version: impl Into<String>) -> Self {
+        Self {
+            version: Some(version.into()),
+            ..self
+        }
+    }
+
+    /// Verify the downloaded asset against the sha256 digest
+    pub fn sha256(mut self, asset_name: impl Into<String>, sha256: impl Into<String>) -> Self {
+        self.sha256.insert(asset_name.into(), sha256.into());
+        self
+    }
+
+    /// Return the url of the prebuilt library, or None if the template needs a version
+    /// and there is none
+    pub fn url(
+        &self,
+        library: &dyn Library,
+        location_version: Option<&str>,
+        context: &LibraryCompilationContext,
+    ) -> Option<String> {
+        self.asset_url(
+            library,
+            &library.prebuilt_library_asset_name(context),
+            location_version,
+            context,
+        )
+    }
+
+    /// Return the url of the prebuilt bundle of the native library prefix, or None if
+    /// the template needs a version and there is none
+    pub fn bundle_url(
+        &self,
+        library: &dyn Library,
+        location_version: Option<&str>,
+        context: &LibraryCompilationContext,
+    ) -> Option<String> {
+        self.asset_url(
+            library,
+            &library.prebuilt_bundle_asset_name(context),
+            location_version,
+            context,
+        )
+    }
+
+    fn asset_url(
+        &self,
+        library: &dyn Library,
+        asset_name: &str,
+        location_version: Option<&str>,
+        context: &LibraryCompilationContext,
+    ) -> Option<String> {
+        let version = self.version.as_deref().or(location_version);
+        if self.template.contains("{version}") && version.is_none() {
+            return None;
+        }
+
+        Some(
+            self.template
+                .replace("{name}", library.name())
+                .replace("{version}", version.unwrap_or_default())
+                .replace("{target}", &context.target().to_string())
+                .replace("{asset}", asset_name),
+        )
+    }
+
+    /// Download and verify the prebuilt library next to the exported library and return its path.
+    /// A failed download or verification is not an error, the library is compiled from sources instead.
+    #[cfg(feature = "downloader")]
+    pub(crate) fn retrieve_prebuilt_library(
+        &self,
+        library: &dyn Library,
+        location_version: Option<&str>,
+        context: &LibraryCompilationContext,
+    ) -> Option<std::path::PathBuf> {
+        let binary_path = library.exported_library_path(context);
+
+        let url = match self.url(library, location_version, context) {
+            Some(url) => url,
+            None => {
+                println!(
+                    "{} has no version to retrieve a prebuilt library from {}",
+                    library.name(),
+                    &self.template
+                );
+                return None;
+            }
+        };
+
+        let asset_name = library.prebuilt_library_asset_name(context);
+        let origin = crate::LibraryOrigin::prebuilt(&url, &asset_name);
+        if origin.is_current(&binary_path) {
+            println!("{} already exists.", binary_path.display());
+            return Some(binary_path);
+        }
+        let sha256 = self.sha256.get(&asset_name).map(|sha256| sha256.as_str());
+
+        let build_directory = binary_path.parent()?;
+        std::fs::create_dir_all(build_directory).ok()?;
+
+        if let Err(error) = crate::components::stage_prebuilt_asset(&binary_path, |directory| {
+            crate::components::fetch_prebuilt_asset(&url, &asset_name, sha256, directory, context)
+        }) {
+            eprintln!("Failed to download {} due to {}", &url, error);
+            return None;
+        }
+        origin.record(&binary_path).ok()?;
+        Some(binary_path)
+    }
+
+    /// Download and verify the prebuilt bundle and unpack it into the native library prefix.
+    /// A failed download or verification is not an error, the library is compiled from sources instead.
+    #[cfg(feature = "tar-location")]
+    pub(crate) fn retrieve_prebuilt_bundle(
+        &self,
+        library: &dyn Library,
+        location_version: Option<&str>,
+        context: &LibraryCompilationContext,
+    ) -> Option<std::path::PathBuf> {
+        let url = self.bundle_url(library, location_version, context)?;
+
+        let asset_name = library.prebuilt_bundle_asset_name(context);
+        let sha256 = self.sha256.get(&asset_name).map(|sha256| sha256.as_str());
+
+        crate::components::retrieve_prebuilt_bundle(library, &url, context, |directory| {
+            crate::components::fetch_prebuilt_asset(&url, &asset_name, sha256, directory, context)
+        })
+    }
+}
@@ -0,0 +1,141 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use minisign_verify::{PublicKey, Signature};
+use user_error::UserFacingError;
+
+use crate::{checksum_asset_name, sha256_file, LibraryCompilationContext};
+
+/// The name of the asset with the minisign signature of a prebuilt asset
+pub fn signature_asset_name(asset_name: &str) -> String {
+    format!("{}.minisig", asset_name)
+}
+
+/// Verify a prebuilt asset before it is used instead of compiling the library.
+/// The asset must match the sha256 digest or, when the digest is not known, the digest in its
+/// companion `.sha256` file. A missing companion is only an error with strict checksums.
+/// When the context has a public key, the asset must also have a valid minisign signature
+/// in its companion `.minisig` file.
+/// `companion` returns the path to a companion file by its asset name, or None if there is none.
+pub(crate) fn verify_prebuilt_asset(
+    asset: &Path,
+    asset_name: &str,
+    sha256: Option<&str>,
+    context: &LibraryCompilationContext,
+    companion: impl Fn(&str) -> Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let checksum_name = checksum_asset_name(asset_name);
+    let expected = match sha256 {
+        Some(sha256) => Some(sha256.trim().to_string()),
+        None => companion(&checksum_name)
+            .map(|checksum| read_checksum(&checksum))
+            .transpose()?,
+    };
+
+    match expected {
+        Some(expected) => {
+            let actual = sha256_file(asset)?;
+            if !actual.eq_ignore_ascii_case(&expected) {
+                return Err(Box::new(
+                    UserFacingError::new("Failed to verify prebuilt library")
+                        .reason(format!("Checksum mismatch of {}", asset_name))
+                        .reason(format!("expected sha256 {}", expected))
+                        .reason(format!("actual sha256   {}", actual))
+                        .help("Publish the prebuilt library together with its checksum"),
+                ));
+            }
+        }
+        None if context.strict_checksums() => {
+            return Err(Box::new(
+                UserFacingError::new("Failed to verify prebuilt library")
+                    .reason(format!("{} has no {}", asset_name, checksum_name))
+                    .help("Publish the prebuilt library together with its checksum"),
+            ));
+        }
+        None => println!(
+            "{} has no {}, not verifying its checksum",
+            asset_name, checksum_name
+        ),
+    }
+
+    if let Some(public_key) = context.prebuilt_public_key() {
+        let signature_name = signature_asset_name(asset_name);
+        let signature = companion(&signature_name).ok_or_else(|| {
+            UserFacingError::new("Failed to verify prebuilt library")
+                .reason(format!("{} has no {}", asset_name, signature_name))
+                .help("Sign the prebuilt library with minisign and publish the signature")
+        })?;
+
+        verify_signature(asset, &signature, public_key).map_err(|error| {
+            UserFacingError::new("Failed to verify prebuilt library")
+                .reason(format!("The signature of {} is not valid", asset_name))
+                .reason(error.to_string())
+                .help("Make sure the prebuilt library is signed with the configured public key")
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Fetch the prebuilt asset into a temporary directory next to the destination,
+/// and move it to the destination only if fetching and verification succeed
+pub(crate) fn stage_prebuilt_asset(
+    destination: &Path,
+    fetch: impl FnOnce(&Path) -> Result<PathBuf, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let name = destination
+        .file_name()
+        .ok_or_else(|| format!("{} has no name", destination.display()))?
+        .to_string_lossy()
+        .to_string();
+    let staging = destination.with_file_name(format!(".{}.download-{}", name, std::process::id()));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::create_dir_all(&staging)?;
+
+    let result = fetch(&staging).and_then(|asset| Ok(std::fs::rename(asset, destination)?));
+
+    std::fs::remove_dir_all(&staging)?;
+    result
+}
+
+/// Download the prebuilt asset and its companion files into the directory and verify it
+#[cfg(feature = "downloader")]
+pub(crate) fn fetch_prebuilt_asset(
+    url: &str,
+    asset_name: &str,
+    sha256: Option<&str>,
+    directory: &Path,
+    context: &LibraryCompilationContext,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let asset = crate::components::download_file(url, sha256, directory, context)?;
+
+    verify_prebuilt_asset(&asset, asset_name, sha256, context, |companion| {
+        // companions are published next to the asset, with an extension appended to its name
+        let companion_url = format!("{}{}", url, companion.strip_prefix(asset_name)?);
+        crate::components::download_file(&companion_url, None, directory, context).ok()
+    })?;
+    Ok(asset)
+}
+
+fn read_checksum(path: &Path) -> Result<String, Box<dyn Error>> {
+    std::fs::read_to_string(path)?
+        .split_whitespace()
+        .next()
+        .map(|checksum| checksum.to_string())
+        .ok_or_else(|| format!("{} is empty", path.display()).into())
+}
+
+/// The public key is either a base64 encoded key or the contents of a minisign.pub file
+fn verify_signature(
+    asset: &Path,
+    signature: &Path,
+    public_key: &str,
+) -> Result<(), Box<dyn Error>> {
+    let public_key =
+        PublicKey::from_base64(public_key.trim()).or_else(|_| PublicKey::decode(public_key))?;
+    let signature = Signature::from_file(signature)?;
+    public_key.verify(&std::fs::read(asset)?, &signature, false)?;
+    Ok(())
+}
@@ -0,0 +1,202 @@
+use std::error::Error;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+
+use user_error::UserFacingError;
+
+use crate::{sha256_file, Library, LibraryCompilationContext};
+
+/// A destination of prebuilt libraries, for example a directory or a GitHub release
+pub trait PrebuiltPublisher: Debug {
+    /// Publish the file under the asset name and return where it is published
+    fn publish(&self, file: &Path, asset_name: &str) -> Result<String, Box<dyn Error>>;
+}
+
+/// Publishes assets into a directory that can be used as
+/// [`PathLocation::prebuilt_library_directory`](crate::PathLocation::prebuilt_library_directory)
+#[derive(Debug, Clone)]
+pub struct DirectoryPublisher {
+    directory: PathBuf,
+}
+
+impl DirectoryPublisher {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+impl PrebuiltPublisher for DirectoryPublisher {
+    fn publish(&self, file: &Path, asset_name: &str) -> Result<String, Box<dyn Error>> {
+        std::fs::create_dir_all(&self.directory)?;
+
+        let published = self.directory.join(asset_name);
+        std::fs::copy(file, &published)?;
+        Ok(published.display().to_string())
+    }
+}
+
+/// Uploads assets to an existing GitHub release with the GitHub CLI `gh`,
+/// replacing assets with the same name
+#[cfg(feature = "downloader")]
+#[derive(Debug, Clone)]
+pub struct GitHubReleasePublisher {
+    owner: String,
+    repo: String,
+    tag: String,
+}
+
+#[cfg(feature = "downloader")]
+impl GitHubReleasePublisher {
+    pub fn new(owner: impl Into<String>, repo: impl Into<String>, tag: impl Into<String>) -> Self {
+        Self {
+            owner: owner.into(),
+            repo: repo.into(),
+            tag: tag.into(),
+        }
+    }
+}
+
+#[cfg(feature = "downloader")]
+impl PrebuiltPublisher for GitHubReleasePublisher {
+    fn publish(&self, file: &Path, asset_name: &str) -> Result<String, Box<dyn Error>> {
+        // gh names the uploaded asset after the file
+        let directory = std::env::temp_dir().join(format!(
+            "shared-library-builder-publish-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory)?;
+        let asset = directory.join(asset_name);
+
+        let repository = format!("{}/{}", &self.owner, &self.repo);
+        let status = std::fs::copy(file, &asset).map(|_| {
+            std::process::Command::new("gh")
+                .arg("release")
+                .arg("upload")
+                .arg(&self.tag)
+                .arg(&asset)
+                .arg("--repo")
+                .arg(&repository)
+                .arg("--clobber")
+                .status()
+        });
+
+        // the staged asset is removed whether the upload succeeded or not
+        if let Err(error) = std::fs::remove_dir_all(&directory) {
+            eprintln!("Failed to remove {} due to {}", directory.display(), error);
+        }
+
+        let failure = match status? {
+            Ok(status) if status.success() => None,
+            Ok(status) => Some(format!("gh exited with {}", status)),
+            Err(error) => Some(format!("Could not run gh: {}", error)),
+        };
+        if let Some(reason) = failure {
+            return Err(Box::new(
+                UserFacingError::new("Failed to publish prebuilt library")
+                    .reason(format!(
+                        "Could not upload {} to the {} release of {}",
+                        asset_name, &self.tag, &repository
+                    ))
+                    .reason(reason)
+                    .help("Make sure the GitHub CLI gh is installed, authenticated and the release exists"),
+            ));
+        }
+
+        Ok(format!(
+            "https://github.com/{}/releases/download/{}/{}",
+            &repository, &self.tag, asset_name
+        ))
+    }
+}
+
+/// Parse a release in the form of `owner/repo@tag`
+#[cfg(feature = "downloader")]
+impl std::str::FromStr for GitHubReleasePublisher {
+    type Err = String;
+
+    fn from_str(release: &str) -> Result<Self, Self::Err> {
+        release
+            .split_once('@')
+            .and_then(|(repository, tag)| {
+                let (owner, repo) = repository.split_once('/')?;
+                (!owner.is_empty() && !repo.is_empty() && !tag.is_empty())
+                    .then(|| Self::new(owner, repo, tag))
+            })
+            .ok_or_else(|| format!("{} is not in the form of owner/repo@tag", release))
+    }
+}
+
+/// The name of the asset with the sha256 digest of a prebuilt asset
+pub fn checksum_asset_name(asset_name: &str) -> String {
+    format!("{}.sha256", asset_name)
+}
+
+/// Publish the result of [`Library::compile`] under the asset name that
+/// [`Library::retrieve_prebuilt_library`] expects, together with a file with its sha256 digest.
+/// Shared libraries are published as is, static libraries as a prebuilt bundle of their prefix.
+/// Return where the assets are published.
+pub fn publish_prebuilt_library(
+    library: &dyn Library,
+    compiled_library: &Path,
+    publisher: &dyn PrebuiltPublisher,
+    context: &LibraryCompilationContext,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let (asset, asset_name) = prebuilt_asset(library, compiled_library, context)?;
+
+    let checksum_name = checksum_asset_name(&asset_name);
+    let checksum = context.build_root().join(&checksum_name);
+    std::fs::write(
+        &checksum,
+        format!("{}  {}\n", sha256_file(&asset)?, &asset_name),
+    )?;
+
+    let published = vec![
+        publisher.publish(&asset, &asset_name)?,
+        publisher.publish(&checksum, &checksum_name)?,
+    ];
+    for each in &published {
+        println!("Published {}", each);
+    }
+    Ok(published)
+}
+
+#[allow(unused_variables)]
+fn prebuilt_asset(
+    library: &dyn Library,
+    compiled_library: &Path,
+    context: &LibraryCompilationContext,
+) -> Result<(PathBuf, String), Box<dyn Error>> {
+    if library.is_header_only() {
+        return Err(Box::new(
+            UserFacingError::new("Failed to publish prebuilt library").reason(format!(
+                "{} only provides headers, there is no prebuilt library",
+                library.name()
+            )),
+        ));
+    }
+
+    if library.is_shared() {
+        return Ok((
+            compiled_library.to_path_buf(),
+            library.prebuilt_library_asset_name(context),
+        ));
+    }
+
+    #[cfg(feature = "tar-location")]
+    return Ok((
+        crate::create_prebuilt_bundle(library, context)?,
+        library.prebuilt_bundle_asset_name(context),
+    ));
+
+    #[cfg(not(feature = "tar-location"))]
+    Err(Box::new(
+        UserFacingError::new("Failed to publish prebuilt library")
+            .reason(format!(
+                "{} is static and is published as a prebuilt bundle",
+                library.name()
+            ))
+            .help("Enable the tar-location feature to create prebuilt bundles"),
+    ))
+}
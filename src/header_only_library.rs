@@ -0,0 +1,187 @@
+use crate::{
+    Library, LibraryCompilationContext, LibraryDependencies, LibraryLocation, LibraryOptions,
+};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use user_error::UserFacingError;
+
+use serde::{Deserialize, Serialize};
+
+/// A library that consists only of headers, for example stb, glm or Eigen.
+/// Selected headers are installed into the `include` directory of the native library prefix
+/// so that dependent libraries find them on the include path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeaderOnlyLibrary {
+    name: String,
+    location: LibraryLocation,
+    /// header files or directories relative to the sources, and where to install them
+    /// relative to the include directory
+    headers: Vec<(PathBuf, PathBuf)>,
+    dependencies: LibraryDependencies,
+    options: LibraryOptions,
+}
+
+impl HeaderOnlyLibrary {
+    pub fn new(name: &str, location: LibraryLocation) -> Self {
+        Self {
+            name: name.to_owned(),
+            location,
+            headers: vec![],
+            dependencies: LibraryDependencies::new(),
+            options: Default::default(),
+        }
+    }
+
+    /// Install a header file or directory keeping its name, for example `glm` -> `include/glm`.
+    /// When no headers are selected, the content of the `include` directory is installed.
+    pub fn with_headers(self, headers: impl Into<PathBuf>) -> Self {
+        let headers = headers.into();
+        let destination = headers
+            .file_name()
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        self.with_headers_into(headers, destination)
+    }
+
+    /// Install a header file or directory under the given path relative to the include directory
+    pub fn with_headers_into(
+        mut self,
+        headers: impl Into<PathBuf>,
+        destination: impl Into<PathBuf>,
+    ) -> Self {
+        self.headers.push((headers.into(), destination.into()));
+        self
+    }
+
+    pub fn depends(mut self, library: Box<dyn Library>) -> Self {
+        self.dependencies = self.dependencies.push(library);
+        self
+    }
+
+    fn include_directory(&self, context: &LibraryCompilationContext) -> PathBuf {
+        self.native_library_prefix(context).join("include")
+    }
+
+    fn selected_headers(&self) -> Vec<(PathBuf, PathBuf)> {
+        if self.headers.is_empty() {
+            vec![(Path::new("include").to_path_buf(), PathBuf::new())]
+        } else {
+            self.headers.clone()
+        }
+    }
+}
+
+#[typetag::serde]
+impl Library for HeaderOnlyLibrary {
+    fn location(&self) -> &LibraryLocation {
+        &self.location
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn retrieve_prebuilt_library(&self, _context: &LibraryCompilationContext) -> Option<PathBuf> {
+        None
+    }
+
+    fn dependencies(&self) -> Option<&LibraryDependencies> {
+        Some(&self.dependencies)
+    }
+
+    fn options(&self) -> &LibraryOptions {
+        &self.options
+    }
+
+    fn options_mut(&mut self) -> &mut LibraryOptions {
+        &mut self.options
+    }
+
+    fn is_header_only(&self) -> bool {
+        true
+    }
+
+    /// Install the selected headers into the include directory of the prefix
+    fn force_compile(&self, context: &LibraryCompilationContext) -> Result<(), Box<dyn Error>> {
+        let source_directory = self.source_directory(context);
+        let include_directory = self.include_directory(context);
+
+        if include_directory.exists() {
+            std::fs::remove_dir_all(&include_directory)?;
+        }
+        std::fs::create_dir_all(&include_directory)?;
+
+        for (headers, destination) in self.selected_headers() {
+            let source = source_directory.join(&headers);
+            let destination = include_directory.join(destination);
+
+            if source.is_file() {
+                if let Some(parent) = destination.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::copy(&source, &destination)?;
+            } else if source.is_dir() {
+                std::fs::create_dir_all(&destination)?;
+                let copy_options = fs_extra::dir::CopyOptions {
+                    content_only: true,
+                    overwrite: true,
+                    ..Default::default()
+                };
+                fs_extra::dir::copy(&source, &destination, &copy_options)?;
+            } else {
+                return Err(Box::new(
+                    UserFacingError::new("Failed to build project")
+                        .reason(format!(
+                            "{} does not contain {}",
+                            source_directory.display(),
+                            headers.display()
+                        ))
+                        .help("Make sure the selected headers exist in the sources"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compiled_library_directories(&self, _context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        vec![]
+    }
+
+    fn ensure_requirements(&self, _context: &LibraryCompilationContext) {}
+
+    fn native_library_prefix(&self, context: &LibraryCompilationContext) -> PathBuf {
+        context.build_root().join(self.name())
+    }
+
+    fn native_library_include_headers(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let directory = self.include_directory(context);
+
+        if directory.exists() {
+            vec![directory]
+        } else {
+            vec![]
+        }
+    }
+
+    fn native_library_linker_libraries(
+        &self,
+        _context: &LibraryCompilationContext,
+    ) -> Vec<PathBuf> {
+        vec![]
+    }
+
+    fn pkg_config_directory(&self, _context: &LibraryCompilationContext) -> Option<PathBuf> {
+        None
+    }
+
+    fn clone_library(&self) -> Box<dyn Library> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+impl From<HeaderOnlyLibrary> for Box<dyn Library> {
+    fn from(library: HeaderOnlyLibrary) -> Self {
+        Box::new(library)
+    }
+}
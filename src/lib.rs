@@ -2,19 +2,29 @@ extern crate fs_extra;
 extern crate to_absolute;
 #[macro_use]
 extern crate strum;
+mod autotools_library;
 #[cfg(feature = "cmake-library")]
 mod cmake_library;
 mod components;
+mod header_only_library;
 mod library;
+mod meson_library;
+mod prebuilt_only_library;
 mod rust_library;
+mod script_library;
 
 pub use components::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub use crate::library::{CompiledLibraryName, CompiledPathBuf, Library};
+pub use autotools_library::AutotoolsLibrary;
 #[cfg(feature = "cmake-library")]
 pub use cmake_library::CMakeLibrary;
-pub use rust_library::RustLibrary;
+pub use header_only_library::HeaderOnlyLibrary;
+pub use meson_library::MesonLibrary;
+pub use prebuilt_only_library::PrebuiltOnlyLibrary;
+pub use rust_library::{CbindgenConfig, RustLibrary};
+pub use script_library::{ScriptLibrary, ScriptStep};
 
 use clap::Parser;
 
@@ -22,18 +32,103 @@ use clap::Parser;
 struct BuildOptions {
     #[clap(long, ignore_case = true)]
     target: Option<LibraryTarget>,
+    /// Check out exactly the git revisions recorded in the lockfile
+    #[clap(long, conflicts_with = "update-lock")]
+    locked: bool,
+    /// Check out the requested git versions and record their revisions in the lockfile
+    #[clap(long)]
+    update_lock: bool,
+    /// Refuse to download archives that have no checksum
+    #[clap(long)]
+    strict_checksums: bool,
+    /// Never access the network, only use existing sources and cached downloads
+    #[clap(long)]
+    offline: bool,
+    /// Download and clone urls that start with a prefix from mirrors, tried in order
+    #[clap(long = "mirror", value_name = "PREFIX=MIRROR[,MIRROR...]")]
+    mirrors: Vec<UrlMirror>,
+    /// Only use prebuilt libraries signed with the minisign public key
+    #[clap(long, value_name = "PUBLIC_KEY")]
+    prebuilt_public_key: Option<String>,
+    /// Copy the compiled library and its sha256 digest into a directory of prebuilt libraries
+    #[clap(long, value_name = "DIRECTORY")]
+    publish: Option<PathBuf>,
+    /// Upload the compiled library and its sha256 digest to an existing GitHub release
+    #[cfg(feature = "downloader")]
+    #[clap(long, value_name = "OWNER/REPO@TAG")]
+    publish_github_release: Option<GitHubReleasePublisher>,
+}
+
+impl BuildOptions {
+    fn lock_mode(&self) -> LockMode {
+        if self.locked {
+            LockMode::Locked
+        } else if self.update_lock {
+            LockMode::Update
+        } else {
+            LockMode::Record
+        }
+    }
+
+    fn configure(&self, context: LibraryCompilationContext) -> LibraryCompilationContext {
+        let context = context
+            .with_lock_mode(self.lock_mode())
+            .with_strict_checksums(self.strict_checksums)
+            .with_offline(self.offline);
+
+        let context = match self.prebuilt_public_key {
+            Some(ref public_key) => context.with_prebuilt_public_key(public_key),
+            None => context,
+        };
+
+        self.mirrors
+            .iter()
+            .cloned()
+            .fold(context, |context, mirror| context.with_mirror(mirror))
+    }
+
+    fn publishers(&self) -> Vec<Box<dyn PrebuiltPublisher>> {
+        let mut publishers: Vec<Box<dyn PrebuiltPublisher>> = vec![];
+        if let Some(ref directory) = self.publish {
+            publishers.push(Box::new(DirectoryPublisher::new(directory)));
+        }
+        #[cfg(feature = "downloader")]
+        if let Some(ref release) = self.publish_github_release {
+            publishers.push(Box::new(release.clone()));
+        }
+        publishers
+    }
+
+    fn publish(
+        &self,
+        library: &dyn Library,
+        compiled_library: &Path,
+        context: &LibraryCompilationContext,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for publisher in self.publishers() {
+            publish_prebuilt_library(library, compiled_library, publisher.as_ref(), context)?;
+        }
+        Ok(())
+    }
 }
 
 pub fn with_target<F>(f: F) -> Result<(), Box<dyn std::error::Error>>
 where
     F: FnOnce(LibraryTarget) -> Result<(), Box<dyn std::error::Error>>,
+{
+    with_build_options(|target, _| f(target))
+}
+
+fn with_build_options<F>(f: F) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnOnce(LibraryTarget, BuildOptions) -> Result<(), Box<dyn std::error::Error>>,
 {
     let options: BuildOptions = BuildOptions::parse();
     let target = options
         .target
         .unwrap_or_else(|| LibraryTarget::for_current_platform());
 
-    f(target)?;
+    f(target, options)?;
     Ok(())
 }
 
@@ -41,7 +136,7 @@ pub fn build_third_party<F>(f: F) -> Result<(), Box<dyn std::error::Error>>
 where
     F: FnOnce(LibraryTarget) -> Result<Box<dyn Library>, Box<dyn std::error::Error>>,
 {
-    with_target(|target| {
+    with_build_options(|target, options| {
         let library = f(target)?;
 
         let target_dir = Path::new("target");
@@ -51,9 +146,11 @@ where
         if !src_dir.exists() {
             std::fs::create_dir_all(src_dir.as_path())?;
         }
-        let context = LibraryCompilationContext::new(src_dir, "target", target, false);
+        let context =
+            options.configure(LibraryCompilationContext::new(src_dir, "target", target, false));
         let compiled_library = library.compile(&context)?;
         println!("Compiled {}", compiled_library.display());
+        options.publish(library.as_ref(), &compiled_library, &context)?;
         Ok(())
     })
 }
@@ -62,7 +159,7 @@ pub fn build_standalone<F>(f: F) -> Result<(), Box<dyn std::error::Error>>
 where
     F: FnOnce(LibraryTarget) -> Result<Box<dyn Library>, Box<dyn std::error::Error>>,
 {
-    with_target(|target| {
+    with_build_options(|target, options| {
         let library = f(target)?;
 
         let target_dir = Path::new("target");
@@ -87,9 +184,11 @@ where
         if !src_dir.exists() {
             std::fs::create_dir_all(src_dir.as_path())?;
         }
-        let context = LibraryCompilationContext::new(src_dir, "target", target, false);
+        let context =
+            options.configure(LibraryCompilationContext::new(src_dir, "target", target, false));
         let compiled_library = library.compile(&context)?;
         println!("Compiled {}", compiled_library.display());
+        options.publish(library.as_ref(), &compiled_library, &context)?;
         Ok(())
     })
 }
@@ -1,5 +1,6 @@
 use crate::{
-    LibraryCompilationContext, LibraryDependencies, LibraryLocation, LibraryOptions, LibraryTarget,
+    prebuilt_bundle_origin, LibraryCompilationContext, LibraryDependencies, LibraryLocation,
+    LibraryOptions, LibraryOrigin, LibraryTarget,
 };
 use serde::{Deserialize, Serialize};
 use std::error::Error;
@@ -107,6 +108,18 @@ pub trait Library: Debug + Send + Sync {
         )
     }
 
+    /// Try to retrieve a prebuilt bundle of the native library prefix (libraries, headers and
+    /// pkg-config files) and return the prefix it is unpacked into
+    fn retrieve_prebuilt_bundle(&self, context: &LibraryCompilationContext) -> Option<PathBuf> {
+        let location = self.release_location();
+        location.retrieve_prebuilt_bundle(self.clone_library(), context)
+    }
+
+    /// Return true if the native library prefix is unpacked from a prebuilt bundle
+    fn has_prebuilt_bundle(&self, context: &LibraryCompilationContext) -> bool {
+        prebuilt_bundle_origin(&self.native_library_prefix(context)).is_some()
+    }
+
     fn dependencies(&self) -> Option<&LibraryDependencies>;
     fn options(&self) -> &LibraryOptions;
     fn options_mut(&mut self) -> &mut LibraryOptions;
@@ -127,11 +140,62 @@ pub trait Library: Debug + Send + Sync {
         self.options_mut().be_static(false);
     }
 
+    /// Return true if the library only provides headers and has nothing to compile or export
+    fn is_header_only(&self) -> bool {
+        false
+    }
+
     fn is_compiled(&self, context: &LibraryCompilationContext) -> bool {
+        if self.is_header_only() {
+            return !self.native_library_include_headers(context).is_empty();
+        }
         self.compiled_library(context).exists()
     }
 
+    /// Return names of this library and its dependencies together with urls of their sources
+    /// that can not be fetched without network access
+    fn unavailable_offline(&self, context: &LibraryCompilationContext) -> Vec<(String, String)> {
+        let mut unavailable = self
+            .dependencies()
+            .map(|dependencies| dependencies.unavailable_offline(context))
+            .unwrap_or_default();
+
+        if let Some(url) = self
+            .location()
+            .unavailable_offline(&PathBuf::from(self.name()), context)
+        {
+            unavailable.push((self.name().to_string(), url));
+        }
+        unavailable
+    }
+
+    /// When building offline make sure that sources of all libraries are available
+    fn ensure_available_offline(
+        &self,
+        context: &LibraryCompilationContext,
+    ) -> Result<(), Box<dyn Error>> {
+        if !context.is_offline() {
+            return Ok(());
+        }
+
+        let unavailable = self.unavailable_offline(context);
+        if unavailable.is_empty() {
+            return Ok(());
+        }
+
+        let mut error = UserFacingError::new("Failed to build project")
+            .reason(format!("{} sources are not available offline:", unavailable.len()));
+        for (library, url) in unavailable {
+            error = error.reason(format!("{}: {}", library, url));
+        }
+        Err(Box::new(error.help(
+            "Build once with network access, or populate the download cache",
+        )))
+    }
+
     fn just_compile(&self, context: &LibraryCompilationContext) -> Result<(), Box<dyn Error>> {
+        self.ensure_available_offline(context)?;
+
         if let Some(dependencies) = self.dependencies() {
             dependencies.ensure_requirements(context)?;
         }
@@ -139,6 +203,8 @@ pub trait Library: Debug + Send + Sync {
         self.ensure_requirements(context);
 
         if let Some(dependencies) = self.dependencies() {
+            // dependencies that are retrieved as prebuilt bundles are not compiled
+            dependencies.retrieve_prebuilt_bundles(context);
             dependencies.ensure_sources(context)?;
         }
         self.ensure_sources(context)?;
@@ -151,7 +217,25 @@ pub trait Library: Debug + Send + Sync {
         Ok(())
     }
 
+    /// The origin of the library compiled from its current sources,
+    /// it is only known once the sources are prepared
+    fn compiled_origin(
+        &self,
+        context: &LibraryCompilationContext,
+    ) -> Result<LibraryOrigin, Box<dyn Error>> {
+        let location = self.location();
+        Ok(LibraryOrigin::compiled(
+            location.fingerprint()?,
+            location.resolved_revision(&PathBuf::from(self.name()), context),
+        ))
+    }
+
     fn compile(&self, context: &LibraryCompilationContext) -> Result<PathBuf, Box<dyn Error>> {
+        if self.is_header_only() {
+            self.just_compile(context)?;
+            return Ok(self.native_library_prefix(context));
+        }
+
         if let Some(prebuilt_library) = self.retrieve_prebuilt_library(context) {
             return Ok(prebuilt_library);
         }
@@ -159,7 +243,10 @@ pub trait Library: Debug + Send + Sync {
         self.just_compile(context)?;
 
         if self.is_shared() {
-            self.export_compiled_library(context)
+            // the origin only tells prebuilt retrieval what the exported library was built from
+            let exported_library = self.export_compiled_library(context)?;
+            self.compiled_origin(context)?.record(&exported_library)?;
+            Ok(exported_library)
         } else {
             Ok(self.compiled_library(context).into_path_buf())
         }
@@ -186,9 +273,20 @@ pub trait Library: Debug + Send + Sync {
             std::fs::copy(compiled_library.as_path(), &exported_path)?;
         }
 
+        for header in self.exported_headers(context) {
+            if let Some(file_name) = header.file_name() {
+                std::fs::copy(&header, exported_directory.join(file_name))?;
+            }
+        }
+
         Ok(exported_path)
     }
 
+    /// Return header files that should be shipped next to the exported shared library
+    fn exported_headers(&self, _context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        vec![]
+    }
+
     fn exported_library_path(&self, context: &LibraryCompilationContext) -> PathBuf {
         context
             .build_root()
@@ -209,10 +307,34 @@ pub trait Library: Debug + Send + Sync {
         )
     }
 
+    fn prebuilt_bundle_asset_name(&self, context: &LibraryCompilationContext) -> String {
+        format!("{}-{}.tar.gz", self.name(), context.target())
+    }
+
     fn compiled_library(&self, context: &LibraryCompilationContext) -> CompiledPathBuf {
         self.compiled_library_named(self.name(), self.compiled_library_name(), context)
     }
 
+    /// Return the compiled library if there is one, unlike [`Library::compiled_library`] it does not panic
+    fn find_compiled_library(&self, context: &LibraryCompilationContext) -> Option<PathBuf> {
+        let compiled_library_name = self.compiled_library_name();
+
+        self.compiled_library_directories(context)
+            .into_iter()
+            .filter_map(|directory| directory.read_dir().ok())
+            .flat_map(|entries| entries.filter_map(|each| each.ok()))
+            .map(|each| each.path())
+            .find(|each| {
+                each.is_file()
+                    && compiled_library_name.matches(
+                        self.name(),
+                        each,
+                        context.target(),
+                        self.is_static(),
+                    )
+            })
+    }
+
     fn compiled_library_named(
         &self,
         library_name: &str,
@@ -278,6 +400,42 @@ pub trait Library: Debug + Send + Sync {
     /// If a native library creates a pkg-config .pc file, return a directory that contains it
     fn pkg_config_directory(&self, context: &LibraryCompilationContext) -> Option<PathBuf>;
 
+    /// pkg-config files of prebuilt libraries refer to the prefix they were built with,
+    /// point them to the native library prefix where they are unpacked
+    fn relocate_pkg_config_files(
+        &self,
+        context: &LibraryCompilationContext,
+    ) -> Result<(), Box<dyn Error>> {
+        let directory = match self.pkg_config_directory(context) {
+            None => return Ok(()),
+            Some(directory) => directory,
+        };
+
+        let prefix = format!("prefix={}", self.native_library_prefix(context).display());
+
+        for entry in directory.read_dir()? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "pc") {
+                continue;
+            }
+
+            let contents = std::fs::read_to_string(&path)?;
+            let relocated = contents
+                .lines()
+                .map(|line| {
+                    if line.starts_with("prefix=") {
+                        prefix.as_str()
+                    } else {
+                        line
+                    }
+                })
+                .collect::<Vec<&str>>()
+                .join("\n");
+            std::fs::write(&path, relocated + "\n")?;
+        }
+        Ok(())
+    }
+
     /// Return true if this native library has dependencies
     fn has_dependencies(&self, _context: &LibraryCompilationContext) -> bool {
         self.dependencies()
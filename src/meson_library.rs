@@ -0,0 +1,429 @@
+use crate::{
+    CompiledLibraryName, Library, LibraryCompilationContext, LibraryDependencies, LibraryLocation,
+    LibraryOptions, LibraryTarget,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use user_error::UserFacingError;
+
+use serde::{Deserialize, Serialize};
+
+/// A library that is configured with `meson setup` and built with `ninja`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MesonLibrary {
+    name: String,
+    exported_name: Option<String>,
+    compiled_name: CompiledLibraryName,
+    source_location: LibraryLocation,
+    release_location: Option<LibraryLocation>,
+    defines: Vec<(String, String)>,
+    dependencies: LibraryDependencies,
+    options: LibraryOptions,
+    env_vars: HashMap<OsString, OsString>,
+    header_directories: Vec<PathBuf>,
+}
+
+impl MesonLibrary {
+    pub fn new(name: &str, location: LibraryLocation) -> Self {
+        Self {
+            name: name.to_owned(),
+            exported_name: None,
+            compiled_name: CompiledLibraryName::Default,
+            source_location: location,
+            release_location: None,
+            defines: vec![],
+            dependencies: LibraryDependencies::new(),
+            options: Default::default(),
+            env_vars: Default::default(),
+            header_directories: vec![Path::new("include").to_path_buf()],
+        }
+    }
+
+    /// Set a project option, passed to `meson setup` as `-D<option>=<value>`
+    pub fn define(mut self, option: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.push((option.into(), value.into()));
+        self
+    }
+
+    pub fn depends(mut self, library: Box<dyn Library>) -> Self {
+        self.dependencies = self.dependencies.push(library);
+        self
+    }
+
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.env_vars.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn compiled_name(mut self, compiled_name: CompiledLibraryName) -> Self {
+        self.compiled_name = compiled_name;
+        self
+    }
+
+    pub fn with_exported_name(mut self, exported_name: impl Into<String>) -> Self {
+        self.exported_name = Some(exported_name.into());
+        self
+    }
+
+    pub fn with_release_location(mut self, release_location: Option<LibraryLocation>) -> Self {
+        self.release_location = release_location;
+        self
+    }
+
+    pub fn with_headers(mut self, header_directory: impl Into<PathBuf>) -> Self {
+        self.header_directories.push(header_directory.into());
+        self
+    }
+
+    fn build_directory(&self, context: &LibraryCompilationContext) -> PathBuf {
+        self.native_library_prefix(context).join("build")
+    }
+
+    /// Return the contents of a meson cross file that describes the toolchain of the target.
+    /// It is only used when the target differs from the current platform.
+    pub fn cross_file(&self, context: &LibraryCompilationContext) -> Result<String, Box<dyn Error>> {
+        let target = context.target();
+
+        let (system, cpu_family) = match target {
+            LibraryTarget::X8664appleDarwin => ("darwin", "x86_64"),
+            LibraryTarget::AArch64appleDarwin => ("darwin", "aarch64"),
+            LibraryTarget::X8664pcWindowsMsvc => ("windows", "x86_64"),
+            LibraryTarget::AArch64pcWindowsMsvc => ("windows", "aarch64"),
+            LibraryTarget::X8664UnknownlinuxGNU => ("linux", "x86_64"),
+            LibraryTarget::AArch64UnknownlinuxGNU => ("linux", "aarch64"),
+            LibraryTarget::AArch64LinuxAndroid => ("android", "aarch64"),
+        };
+
+        let mut compiler_args: Vec<String> = vec![];
+
+        let binaries: [(&str, String); 4] = if context.is_mac() {
+            let arch = match target {
+                LibraryTarget::AArch64appleDarwin => "arm64",
+                _ => "x86_64",
+            };
+            compiler_args.extend([
+                "-arch".to_string(),
+                arch.to_string(),
+                format!("-mmacosx-version-min={}", context.macos_target_version()),
+            ]);
+            [
+                ("c", "clang".to_string()),
+                ("cpp", "clang++".to_string()),
+                ("ar", "ar".to_string()),
+                ("strip", "strip".to_string()),
+            ]
+        } else if context.is_android() {
+            let toolchain = android_toolchain_directory()?;
+            let compiler = format!("{}{}", target, context.android_target_api());
+            [
+                (
+                    "c",
+                    toolchain.join(format!("{}-clang", compiler)).display().to_string(),
+                ),
+                (
+                    "cpp",
+                    toolchain.join(format!("{}-clang++", compiler)).display().to_string(),
+                ),
+                ("ar", toolchain.join("llvm-ar").display().to_string()),
+                ("strip", toolchain.join("llvm-strip").display().to_string()),
+            ]
+        } else if context.is_windows() {
+            [
+                ("c", "cl".to_string()),
+                ("cpp", "cl".to_string()),
+                ("ar", "lib".to_string()),
+                ("strip", "strip".to_string()),
+            ]
+        } else {
+            let prefix = format!("{}-linux-gnu", cpu_family);
+            [
+                ("c", format!("{}-gcc", prefix)),
+                ("cpp", format!("{}-g++", prefix)),
+                ("ar", format!("{}-ar", prefix)),
+                ("strip", format!("{}-strip", prefix)),
+            ]
+        };
+
+        let mut cross_file = String::from("[binaries]\n");
+        for (tool, binary) in binaries {
+            cross_file.push_str(&format!("{} = '{}'\n", tool, binary));
+        }
+        cross_file.push_str("pkg-config = 'pkg-config'\n");
+
+        if !compiler_args.is_empty() {
+            let args = compiler_args
+                .iter()
+                .map(|arg| format!("'{}'", arg))
+                .collect::<Vec<String>>()
+                .join(", ");
+            cross_file.push_str("\n[built-in options]\n");
+            for option in ["c_args", "cpp_args", "c_link_args", "cpp_link_args"] {
+                cross_file.push_str(&format!("{} = [{}]\n", option, args));
+            }
+        }
+
+        cross_file.push_str("\n[host_machine]\n");
+        cross_file.push_str(&format!("system = '{}'\n", system));
+        cross_file.push_str(&format!("cpu_family = '{}'\n", cpu_family));
+        cross_file.push_str(&format!("cpu = '{}'\n", cpu_family));
+        cross_file.push_str("endian = 'little'\n");
+
+        Ok(cross_file)
+    }
+
+    fn run(&self, mut command: Command, action: &str) -> Result<(), Box<dyn Error>> {
+        println!("{:?}", &command);
+        let status = command.status()?;
+
+        if !status.success() {
+            return Err(Box::new(
+                UserFacingError::new("Failed to build project")
+                    .reason(format!("Could not {} {}", action, self.name()))
+                    .help("Inspect the output above for the failing command"),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl Library for MesonLibrary {
+    fn location(&self) -> &LibraryLocation {
+        &self.source_location
+    }
+
+    fn release_location(&self) -> &LibraryLocation {
+        self.release_location
+            .as_ref()
+            .unwrap_or(&self.source_location)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compiled_library_name(&self) -> CompiledLibraryName {
+        self.compiled_name.clone()
+    }
+
+    fn exported_name(&self) -> &str {
+        self.exported_name.as_deref().unwrap_or_else(|| self.name())
+    }
+
+    fn dependencies(&self) -> Option<&LibraryDependencies> {
+        Some(&self.dependencies)
+    }
+
+    fn options(&self) -> &LibraryOptions {
+        &self.options
+    }
+
+    fn options_mut(&mut self) -> &mut LibraryOptions {
+        &mut self.options
+    }
+
+    fn force_compile(&self, context: &LibraryCompilationContext) -> Result<(), Box<dyn Error>> {
+        let out_dir = self.native_library_prefix(context);
+        let build_directory = self.build_directory(context);
+        if !build_directory.exists() {
+            std::fs::create_dir_all(&build_directory)?;
+        }
+
+        let mut setup = Command::new("meson");
+        setup.arg("setup");
+
+        // meson refuses to set up an already configured build directory
+        if build_directory.join("meson-private").exists() {
+            setup.arg("--reconfigure");
+        }
+
+        setup
+            .arg(&build_directory)
+            .arg(self.source_directory(context))
+            .arg(format!("--prefix={}", out_dir.display()))
+            .arg("--libdir=lib")
+            .arg(format!("--buildtype={}", context.profile()))
+            .arg(format!(
+                "--default-library={}",
+                if self.is_static() { "static" } else { "shared" }
+            ));
+
+        // the target may come from CARGO_BUILD_TARGET, so it is compared with the host itself
+        if context.target() != &LibraryTarget::for_current_host() {
+            let cross_file = build_directory.join("cross-file.txt");
+            std::fs::write(&cross_file, self.cross_file(context)?)?;
+            setup.arg("--cross-file").arg(&cross_file);
+        }
+
+        let mut cmake_prefix_paths = self.all_native_library_prefixes(context);
+        if let Ok(ref path) = std::env::var("CMAKE_PREFIX_PATH") {
+            cmake_prefix_paths.push(Path::new(path).to_path_buf());
+        }
+        if !cmake_prefix_paths.is_empty() {
+            setup.arg(format!(
+                "--cmake-prefix-path={}",
+                join_option_values(&cmake_prefix_paths)
+            ));
+        }
+
+        // the build runs the same tools as the configuration, so both get the same environment
+        let mut environment: Vec<(OsString, OsString)> = vec![];
+
+        let mut pkg_config_paths = vec![];
+        if let Ok(ref path) = std::env::var("PKG_CONFIG_PATH") {
+            pkg_config_paths.extend(std::env::split_paths(path));
+        }
+        pkg_config_paths.extend(self.all_pkg_config_directories(context));
+        if !pkg_config_paths.is_empty() {
+            setup.arg(format!(
+                "--pkg-config-path={}",
+                join_option_values(&pkg_config_paths)
+            ));
+            environment.push((
+                "PKG_CONFIG_PATH".into(),
+                std::env::join_paths(&pkg_config_paths)?,
+            ));
+        }
+
+        for (option, value) in &self.defines {
+            setup.arg(format!("-D{}={}", option, value));
+        }
+
+        if context.is_mac() {
+            environment.push((
+                "MACOSX_DEPLOYMENT_TARGET".into(),
+                context.macos_target_version().into(),
+            ));
+        }
+
+        environment.extend(self.all_native_library_vars(context));
+        environment.extend(self.env_vars.iter().map(|(k, v)| (k.clone(), v.clone())));
+        setup.envs(environment.iter().cloned());
+
+        println!(
+            "Building Meson library for target = {:?}",
+            &context.target().to_string()
+        );
+
+        self.run(setup, "configure")?;
+
+        let mut install = Command::new("ninja");
+        install
+            .arg("-C")
+            .arg(&build_directory)
+            .arg(format!("-j{}", context.jobs()))
+            .arg("install")
+            .envs(environment);
+        self.run(install, "build and install")?;
+
+        Ok(())
+    }
+
+    fn compiled_library_directories(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let lib_dir = self.native_library_prefix(context).join("lib");
+        let bin_dir = self.native_library_prefix(context).join("bin");
+        vec![lib_dir, bin_dir]
+    }
+
+    fn ensure_requirements(&self, _context: &LibraryCompilationContext) {
+        which::which("meson").expect("Meson projects require meson, make sure it is installed");
+        which::which("ninja").expect("Meson projects require ninja, make sure it is installed");
+    }
+
+    fn native_library_prefix(&self, context: &LibraryCompilationContext) -> PathBuf {
+        context.build_root().join(self.name())
+    }
+
+    fn native_library_include_headers(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let mut dirs = vec![];
+
+        for header_dir in &self.header_directories {
+            let directory = self.native_library_prefix(context).join(header_dir);
+
+            if directory.exists() {
+                dirs.push(directory);
+            }
+        }
+
+        dirs
+    }
+
+    fn native_library_linker_libraries(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let mut dirs = vec![];
+
+        let directory = self.native_library_prefix(context).join("lib");
+
+        if directory.exists() {
+            dirs.push(directory);
+        }
+
+        dirs
+    }
+
+    fn pkg_config_directory(&self, context: &LibraryCompilationContext) -> Option<PathBuf> {
+        let directory = self
+            .native_library_prefix(context)
+            .join("lib")
+            .join("pkgconfig");
+
+        if directory.exists() {
+            return Some(directory);
+        }
+
+        let directory = self
+            .native_library_prefix(context)
+            .join("share")
+            .join("pkgconfig");
+
+        if directory.exists() {
+            return Some(directory);
+        }
+
+        None
+    }
+
+    fn clone_library(&self) -> Box<dyn Library> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+impl From<MesonLibrary> for Box<dyn Library> {
+    fn from(library: MesonLibrary) -> Self {
+        Box::new(library)
+    }
+}
+
+/// Meson array options are passed on the command line as comma separated values
+fn join_option_values(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|each| each.display().to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn android_toolchain_directory() -> Result<PathBuf, Box<dyn Error>> {
+    let ndk_root = std::env::var("ANDROID_NDK")
+        .or_else(|_| std::env::var("NDK_HOME"))
+        .map_err(|_| {
+            UserFacingError::new("Failed to build project")
+                .reason("ANDROID_NDK or NDK_HOME must be defined")
+                .help("Point ANDROID_NDK to the root of the Android NDK")
+        })?;
+
+    let host_tag = match std::env::consts::OS {
+        "macos" => "darwin-x86_64",
+        "windows" => "windows-x86_64",
+        _ => "linux-x86_64",
+    };
+
+    Ok(PathBuf::from(ndk_root)
+        .join("toolchains")
+        .join("llvm")
+        .join("prebuilt")
+        .join(host_tag)
+        .join("bin"))
+}
@@ -0,0 +1,320 @@
+use crate::{
+    CompiledLibraryName, Library, LibraryCompilationContext, LibraryDependencies, LibraryLocation,
+    LibraryOptions, LibraryOrigin, LibraryTarget,
+};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use user_error::UserFacingError;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// A library that is never compiled from sources. Instead, a binary distribution
+/// (for example a tarball with `lib/` and `include/` folders) is provided for each target
+/// and unpacked into the native library prefix.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrebuiltOnlyLibrary {
+    name: String,
+    exported_name: Option<String>,
+    compiled_name: CompiledLibraryName,
+    #[serde(deserialize_with = "deserialize_artifacts")]
+    artifacts: Vec<(LibraryTarget, LibraryLocation)>,
+    options: LibraryOptions,
+    header_directories: Vec<PathBuf>,
+}
+
+impl PrebuiltOnlyLibrary {
+    pub fn new(name: &str, target: LibraryTarget, location: LibraryLocation) -> Self {
+        Self {
+            name: name.to_owned(),
+            exported_name: None,
+            compiled_name: CompiledLibraryName::Default,
+            artifacts: vec![(target, location)],
+            options: Default::default(),
+            header_directories: vec![Path::new("include").to_path_buf()],
+        }
+    }
+
+    /// Provide a binary distribution for another target
+    pub fn artifact(mut self, target: LibraryTarget, location: LibraryLocation) -> Self {
+        self.artifacts.retain(|(each, _)| each != &target);
+        self.artifacts.push((target, location));
+        self
+    }
+
+    pub fn compiled_name(mut self, compiled_name: CompiledLibraryName) -> Self {
+        self.compiled_name = compiled_name;
+        self
+    }
+
+    pub fn with_exported_name(mut self, exported_name: impl Into<String>) -> Self {
+        self.exported_name = Some(exported_name.into());
+        self
+    }
+
+    pub fn with_headers(mut self, header_directory: impl Into<PathBuf>) -> Self {
+        self.header_directories.push(header_directory.into());
+        self
+    }
+
+    fn artifact_for(&self, target: &LibraryTarget) -> Option<&LibraryLocation> {
+        self.artifacts
+            .iter()
+            .find(|(each, _)| each == target)
+            .map(|(_, location)| location)
+    }
+
+    /// Return the binary distribution of the target of the compilation context
+    fn artifact_location(
+        &self,
+        context: &LibraryCompilationContext,
+    ) -> Result<&LibraryLocation, Box<dyn Error>> {
+        self.artifact_for(context.target()).ok_or_else(|| {
+            UserFacingError::new("Failed to build project")
+                .reason(format!(
+                    "There is no prebuilt {} for {}",
+                    self.name(),
+                    context.target()
+                ))
+                .help(format!(
+                    "{} can not be compiled from sources, it is only available for {}",
+                    self.name(),
+                    self.artifacts
+                        .iter()
+                        .map(|(target, _)| target.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ))
+                .into()
+        })
+    }
+
+    /// Unpack the binary distribution once and return the library, exported if it is shared
+    fn unpack_library(
+        &self,
+        context: &LibraryCompilationContext,
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        self.ensure_sources(context)?;
+        self.force_compile(context)?;
+        let compiled_library = self.find_compiled_library(context).unwrap();
+
+        if self.is_static() {
+            return Ok(compiled_library);
+        }
+
+        let exported_library = self.exported_library_path(context);
+        if let Some(exported_directory) = exported_library.parent() {
+            std::fs::create_dir_all(exported_directory)?;
+        }
+        if compiled_library != exported_library {
+            std::fs::copy(&compiled_library, &exported_library)?;
+        }
+        Ok(exported_library)
+    }
+}
+
+/// The builder always provides an artifact, so a deserialized library must provide one too
+fn deserialize_artifacts<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(LibraryTarget, LibraryLocation)>, D::Error> {
+    let artifacts = Vec::<(LibraryTarget, LibraryLocation)>::deserialize(deserializer)?;
+    if artifacts.is_empty() {
+        return Err(serde::de::Error::custom(
+            "a prebuilt only library needs at least one artifact",
+        ));
+    }
+    Ok(artifacts)
+}
+
+#[typetag::serde]
+impl Library for PrebuiltOnlyLibrary {
+    /// Return the binary distribution of the current platform, or the first one
+    /// Without a compilation context this is the distribution of the current platform,
+    /// everything that builds uses the distribution of the context target instead
+    fn location(&self) -> &LibraryLocation {
+        self.artifact_for(&LibraryTarget::for_current_platform())
+            .unwrap_or_else(|| &self.artifacts[0].1)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compiled_library_name(&self) -> CompiledLibraryName {
+        self.compiled_name.clone()
+    }
+
+    fn exported_name(&self) -> &str {
+        self.exported_name.as_deref().unwrap_or_else(|| self.name())
+    }
+
+    fn source_directory(&self, context: &LibraryCompilationContext) -> PathBuf {
+        self.native_library_prefix(context)
+    }
+
+    /// Unpack the binary distribution of the target into the native library prefix
+    fn ensure_sources(&self, context: &LibraryCompilationContext) -> Result<(), Box<dyn Error>> {
+        let location = self.artifact_location(context)?;
+
+        let prefix = self.native_library_prefix(context);
+        location.ensure_sources(&prefix, context)?;
+
+        let unpacked = location.sources_directory(&prefix, context);
+        if unpacked != prefix {
+            std::fs::create_dir_all(&prefix)?;
+            let copy_options = fs_extra::dir::CopyOptions {
+                content_only: true,
+                overwrite: true,
+                ..Default::default()
+            };
+            fs_extra::dir::copy(&unpacked, &prefix, &copy_options)?;
+        }
+
+        self.relocate_pkg_config_files(context)
+    }
+
+    fn unavailable_offline(&self, context: &LibraryCompilationContext) -> Vec<(String, String)> {
+        self.artifact_for(context.target())
+            .and_then(|location| {
+                location.unavailable_offline(&self.native_library_prefix(context), context)
+            })
+            .map(|url| vec![(self.name().to_string(), url)])
+            .unwrap_or_default()
+    }
+
+    fn retrieve_prebuilt_library(&self, context: &LibraryCompilationContext) -> Option<PathBuf> {
+        self.unpack_library(context)
+            .map_err(|error| {
+                eprintln!(
+                    "Failed to retrieve prebuilt {} due to {}",
+                    self.name(),
+                    error
+                )
+            })
+            .ok()
+    }
+
+    fn retrieve_prebuilt_bundle(&self, context: &LibraryCompilationContext) -> Option<PathBuf> {
+        self.artifact_for(context.target())?
+            .retrieve_prebuilt_bundle(self.clone_library(), context)
+    }
+
+    fn compiled_origin(
+        &self,
+        context: &LibraryCompilationContext,
+    ) -> Result<LibraryOrigin, Box<dyn Error>> {
+        let location = self.artifact_location(context)?;
+        Ok(LibraryOrigin::compiled(
+            location.fingerprint()?,
+            location.resolved_revision(&self.native_library_prefix(context), context),
+        ))
+    }
+
+    /// There is nothing to compile, the binary distribution of the target is only unpacked
+    fn compile(&self, context: &LibraryCompilationContext) -> Result<PathBuf, Box<dyn Error>> {
+        self.ensure_available_offline(context)?;
+        self.unpack_library(context)
+    }
+
+    fn dependencies(&self) -> Option<&LibraryDependencies> {
+        None
+    }
+
+    fn options(&self) -> &LibraryOptions {
+        &self.options
+    }
+
+    fn options_mut(&mut self) -> &mut LibraryOptions {
+        &mut self.options
+    }
+
+    /// There is nothing to compile, make sure the unpacked distribution contains the library
+    fn force_compile(&self, context: &LibraryCompilationContext) -> Result<(), Box<dyn Error>> {
+        if self.find_compiled_library(context).is_none() {
+            return Err(Box::new(
+                UserFacingError::new("Failed to build project")
+                    .reason(format!(
+                        "The prebuilt {} for {} does not contain {}",
+                        self.name(),
+                        context.target(),
+                        self.compiled_library_name().file_name(
+                            self.name(),
+                            context.target(),
+                            self.is_static()
+                        )
+                    ))
+                    .help("Make sure the binary distribution has lib/ or bin/ folders"),
+            ));
+        }
+        Ok(())
+    }
+
+    fn compiled_library_directories(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let lib_dir = self.native_library_prefix(context).join("lib");
+        let bin_dir = self.native_library_prefix(context).join("bin");
+        vec![lib_dir, bin_dir]
+    }
+
+    fn ensure_requirements(&self, _context: &LibraryCompilationContext) {}
+
+    fn native_library_prefix(&self, context: &LibraryCompilationContext) -> PathBuf {
+        context.build_root().join(self.name())
+    }
+
+    fn native_library_include_headers(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let mut dirs = vec![];
+
+        for header_dir in &self.header_directories {
+            let directory = self.native_library_prefix(context).join(header_dir);
+
+            if directory.exists() {
+                dirs.push(directory);
+            }
+        }
+
+        dirs
+    }
+
+    fn native_library_linker_libraries(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let mut dirs = vec![];
+
+        let directory = self.native_library_prefix(context).join("lib");
+
+        if directory.exists() {
+            dirs.push(directory);
+        }
+
+        dirs
+    }
+
+    fn pkg_config_directory(&self, context: &LibraryCompilationContext) -> Option<PathBuf> {
+        let directory = self
+            .native_library_prefix(context)
+            .join("lib")
+            .join("pkgconfig");
+
+        if directory.exists() {
+            return Some(directory);
+        }
+
+        let directory = self
+            .native_library_prefix(context)
+            .join("share")
+            .join("pkgconfig");
+
+        if directory.exists() {
+            return Some(directory);
+        }
+
+        None
+    }
+
+    fn clone_library(&self) -> Box<dyn Library> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+impl From<PrebuiltOnlyLibrary> for Box<dyn Library> {
+    fn from(library: PrebuiltOnlyLibrary) -> Self {
+        Box::new(library)
+    }
+}
@@ -9,6 +9,7 @@ use std::ffi::OsString;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use user_error::UserFacingError;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RustLibrary {
@@ -19,6 +20,21 @@ pub struct RustLibrary {
     env_vars: Vec<(OsString, OsString)>,
     options: LibraryOptions,
     package: Option<String>,
+    #[serde(default)]
+    cargo_c: bool,
+    #[serde(default)]
+    cbindgen: Option<CbindgenConfig>,
+}
+
+/// Configuration used to generate a C header of the crate with cbindgen
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum CbindgenConfig {
+    /// Use `cbindgen.toml` from the root of the crate if it exists
+    Crate,
+    /// Use a configuration file, relative to the root of the crate
+    File(PathBuf),
+    /// Use the given contents of a `cbindgen.toml`
+    Inline(String),
 }
 
 impl RustLibrary {
@@ -31,6 +47,8 @@ impl RustLibrary {
             env_vars: vec![],
             options: Default::default(),
             package: None,
+            cargo_c: false,
+            cbindgen: None,
         }
     }
 
@@ -64,6 +82,80 @@ impl RustLibrary {
         library
     }
 
+    /// Build and install the crate with `cargo cinstall` (cargo-c) into the native library prefix.
+    /// This way the generated C header, pkg-config file and the libraries can be consumed by
+    /// the dependent native libraries just like any other native dependency.
+    pub fn cargo_c(self) -> Self {
+        let mut library = self;
+        library.cargo_c = true;
+        library
+    }
+
+    /// Generate a C header `<name>.h` with cbindgen after the crate is compiled
+    pub fn cbindgen(self, config: CbindgenConfig) -> Self {
+        let mut library = self;
+        library.cbindgen = Some(config);
+        library
+    }
+
+    /// Return a path to the header generated by cbindgen
+    pub fn header_path(&self, context: &LibraryCompilationContext) -> PathBuf {
+        self.native_library_prefix(context)
+            .join("include")
+            .join(format!("{}.h", self.name()))
+    }
+
+    fn generate_header(&self, context: &LibraryCompilationContext) -> Result<(), Box<dyn Error>> {
+        let config = match &self.cbindgen {
+            None => return Ok(()),
+            Some(config) => config,
+        };
+
+        let crate_directory = self.crate_source_directory(context);
+        let header = self.header_path(context);
+        if let Some(header_directory) = header.parent() {
+            fs::create_dir_all(header_directory)?;
+        }
+
+        let mut command = Command::new("cbindgen");
+        command.current_dir(&crate_directory);
+
+        match config {
+            CbindgenConfig::Crate => {
+                let config_file = crate_directory.join("cbindgen.toml");
+                if config_file.exists() {
+                    command.arg("--config").arg(config_file);
+                }
+            }
+            CbindgenConfig::File(file) => {
+                command.arg("--config").arg(crate_directory.join(file));
+            }
+            CbindgenConfig::Inline(contents) => {
+                let config_file = context
+                    .build_root()
+                    .join(format!("{}.cbindgen.toml", self.name()));
+                fs::write(&config_file, contents)?;
+                command.arg("--config").arg(config_file);
+            }
+        }
+
+        if let Some(package) = &self.package {
+            command.arg("--crate").arg(package);
+        }
+
+        command.arg("--output").arg(&header).arg(&crate_directory);
+
+        let status = command.status()?;
+        if !status.success() {
+            return Err(Box::new(
+                UserFacingError::new("Failed to build project")
+                    .reason(format!("Could not generate a C header for {}", self.name))
+                    .help("Make sure cbindgen is installed and its configuration is correct"),
+            ));
+        }
+        Ok(())
+    }
+
     fn crate_source_directory(&self, context: &LibraryCompilationContext) -> PathBuf {
         self.source_directory(context)
     }
@@ -194,17 +286,30 @@ impl Library for RustLibrary {
                 .map(|(k, v)| (k.as_os_str(), v.as_os_str())),
         );
 
-        if context.is_android() {
-            command.arg("apk").arg("--");
+        if self.cargo_c {
+            let prefix = self.native_library_prefix(context);
+            command
+                .arg("cinstall")
+                .arg("--prefix")
+                .arg(&prefix)
+                .arg("--libdir")
+                .arg(prefix.join("lib"))
+                .arg("--library-type")
+                .arg(if self.is_static() { "staticlib" } else { "cdylib" });
+        } else {
+            if context.is_android() {
+                command.arg("apk").arg("--");
+            }
+            command.arg("build");
         }
 
-        command.arg("build");
-
         if let Some(package) = &self.package {
             command.arg("--package").arg(package);
         }
 
-        command.arg("--lib");
+        if !self.cargo_c {
+            command.arg("--lib");
+        }
 
         if !context.target().is_current() {
             command.arg("--target").arg(context.target().to_string());
@@ -221,6 +326,9 @@ impl Library for RustLibrary {
 
         if context.is_release() {
             command.arg("--release");
+        } else if self.cargo_c {
+            // cargo cinstall builds in release mode by default
+            command.arg("--debug");
         }
 
         if context.is_windows() {
@@ -243,10 +351,16 @@ impl Library for RustLibrary {
         if !status.success() {
             panic!("Could not compile {}", self.name);
         }
-        Ok(())
+
+        self.generate_header(context)
     }
 
     fn compiled_library_directories(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        if self.cargo_c {
+            let prefix = self.native_library_prefix(context);
+            return vec![prefix.join("lib"), prefix.join("bin")];
+        }
+
         let path_without_target = context.build_root().join(context.profile());
 
         let path_with_target = context
@@ -262,6 +376,18 @@ impl Library for RustLibrary {
     }
 
     fn ensure_requirements(&self, _options: &LibraryCompilationContext) {
+        if self.cbindgen.is_some() {
+            which::which("cbindgen").expect(
+                "cbindgen is required to generate a header, install it with `cargo install cbindgen`",
+            );
+        }
+
+        if self.cargo_c {
+            which::which("cargo-cinstall").expect(
+                "cargo-c is required to build the library, install it with `cargo install cargo-c`",
+            );
+        }
+
         self.requires.iter().for_each(|each| {
             which::which(each).unwrap_or_else(|_| {
                 let key = "PATH";
@@ -283,22 +409,59 @@ impl Library for RustLibrary {
     }
 
     fn native_library_prefix(&self, context: &LibraryCompilationContext) -> PathBuf {
+        if self.cargo_c {
+            return context.build_root().join(self.name());
+        }
         context.build_root().to_path_buf()
     }
 
-    fn native_library_include_headers(&self, _context: &LibraryCompilationContext) -> Vec<PathBuf> {
-        vec![]
+    fn native_library_include_headers(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let mut dirs = vec![];
+
+        if self.cargo_c || self.cbindgen.is_some() {
+            let directory = self.native_library_prefix(context).join("include");
+            if directory.exists() {
+                dirs.push(directory);
+            }
+        }
+
+        dirs
+    }
+
+    fn exported_headers(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let header = self.header_path(context);
+
+        if self.cbindgen.is_some() && header.exists() {
+            vec![header]
+        } else {
+            vec![]
+        }
     }
 
-    fn native_library_linker_libraries(
-        &self,
-        _context: &LibraryCompilationContext,
-    ) -> Vec<PathBuf> {
-        vec![]
+    fn native_library_linker_libraries(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let mut dirs = vec![];
+
+        if self.cargo_c {
+            let directory = self.native_library_prefix(context).join("lib");
+            if directory.exists() {
+                dirs.push(directory);
+            }
+        }
+
+        dirs
     }
 
-    fn pkg_config_directory(&self, _context: &LibraryCompilationContext) -> Option<PathBuf> {
-        None
+    fn pkg_config_directory(&self, context: &LibraryCompilationContext) -> Option<PathBuf> {
+        if !self.cargo_c {
+            return None;
+        }
+
+        let directory = self
+            .native_library_prefix(context)
+            .join("lib")
+            .join("pkgconfig");
+
+        directory.exists().then_some(directory)
     }
 
     fn clone_library(&self) -> Box<dyn Library> {
@@ -0,0 +1,335 @@
+use crate::{
+    CompiledLibraryName, Library, LibraryCompilationContext, LibraryDependencies, LibraryLocation,
+    LibraryOptions,
+};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use user_error::UserFacingError;
+
+use serde::{Deserialize, Serialize};
+
+/// A command executed by the [`ScriptLibrary`] to build the library.
+///
+/// The program, arguments, working directory and environment values may contain placeholders
+/// that are replaced before running the command:
+///  - `{prefix}` the native library prefix where the library should be installed
+///  - `{source}` the sources directory of the library
+///  - `{target}` the target triple, for example `x86_64-unknown-linux-gnu`
+///  - `{profile}` either `debug` or `release`
+///  - `{jobs}` the amount of parallel jobs
+///  - `{dep_prefixes}` native library prefixes of all dependencies joined with the platform path separator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptStep {
+    program: String,
+    #[serde(default)]
+    args: Vec<String>,
+    /// working directory relative to the sources directory
+    #[serde(default)]
+    directory: Option<PathBuf>,
+    #[serde(default)]
+    env: Vec<(String, String)>,
+}
+
+impl ScriptStep {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: vec![],
+            directory: None,
+            env: vec![],
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args(mut self, args: Vec<&str>) -> Self {
+        self.args.extend(args.iter().map(|each| each.to_string()));
+        self
+    }
+
+    /// Run the command in a directory relative to the sources of the library
+    pub fn directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+}
+
+impl std::fmt::Display for ScriptStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// A library that is built by running a user defined list of commands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptLibrary {
+    name: String,
+    exported_name: Option<String>,
+    compiled_name: CompiledLibraryName,
+    source_location: LibraryLocation,
+    release_location: Option<LibraryLocation>,
+    steps: Vec<ScriptStep>,
+    dependencies: LibraryDependencies,
+    options: LibraryOptions,
+    /// directories relative to the prefix that contain the compiled libraries
+    library_directories: Vec<PathBuf>,
+    /// directories relative to the prefix that contain the headers
+    header_directories: Vec<PathBuf>,
+    /// directory relative to the prefix that contains the pkg-config files
+    pkg_config_directory: Option<PathBuf>,
+}
+
+impl ScriptLibrary {
+    pub fn new(name: &str, location: LibraryLocation) -> Self {
+        Self {
+            name: name.to_owned(),
+            exported_name: None,
+            compiled_name: CompiledLibraryName::Default,
+            source_location: location,
+            release_location: None,
+            steps: vec![],
+            dependencies: LibraryDependencies::new(),
+            options: Default::default(),
+            library_directories: vec![Path::new("lib").to_path_buf()],
+            header_directories: vec![Path::new("include").to_path_buf()],
+            pkg_config_directory: Some(Path::new("lib").join("pkgconfig")),
+        }
+    }
+
+    pub fn step(mut self, step: ScriptStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn depends(mut self, library: Box<dyn Library>) -> Self {
+        self.dependencies = self.dependencies.push(library);
+        self
+    }
+
+    pub fn compiled_name(mut self, compiled_name: CompiledLibraryName) -> Self {
+        self.compiled_name = compiled_name;
+        self
+    }
+
+    pub fn with_exported_name(mut self, exported_name: impl Into<String>) -> Self {
+        self.exported_name = Some(exported_name.into());
+        self
+    }
+
+    pub fn with_release_location(mut self, release_location: Option<LibraryLocation>) -> Self {
+        self.release_location = release_location;
+        self
+    }
+
+    /// Add a directory, relative to the prefix, where the steps put the compiled libraries
+    pub fn with_library_directory(mut self, library_directory: impl Into<PathBuf>) -> Self {
+        self.library_directories.push(library_directory.into());
+        self
+    }
+
+    /// Add a directory, relative to the prefix, where the steps put the headers
+    pub fn with_headers(mut self, header_directory: impl Into<PathBuf>) -> Self {
+        self.header_directories.push(header_directory.into());
+        self
+    }
+
+    /// Set a directory, relative to the prefix, where the steps put the pkg-config files
+    pub fn with_pkg_config_directory(mut self, directory: Option<PathBuf>) -> Self {
+        self.pkg_config_directory = directory;
+        self
+    }
+
+    fn expand(&self, value: &str, context: &LibraryCompilationContext) -> String {
+        let dependency_prefixes =
+            std::env::join_paths(self.all_native_library_prefixes(context))
+                .map(|paths| paths.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+        value
+            .replace(
+                "{prefix}",
+                &self.native_library_prefix(context).display().to_string(),
+            )
+            .replace(
+                "{source}",
+                &self.source_directory(context).display().to_string(),
+            )
+            .replace("{target}", &context.target().to_string())
+            .replace("{profile}", context.profile())
+            .replace("{jobs}", &context.jobs().to_string())
+            .replace("{dep_prefixes}", &dependency_prefixes)
+    }
+
+    fn step_command(&self, step: &ScriptStep, context: &LibraryCompilationContext) -> Command {
+        let source_directory = self.source_directory(context);
+
+        let mut command = Command::new(self.expand(&step.program, context));
+        command.args(step.args.iter().map(|arg| self.expand(arg, context)));
+        command.current_dir(match &step.directory {
+            None => source_directory,
+            Some(directory) => {
+                source_directory.join(self.expand(&directory.to_string_lossy(), context))
+            }
+        });
+
+        let mut pkg_config_paths = vec![];
+        if let Ok(ref path) = std::env::var("PKG_CONFIG_PATH") {
+            pkg_config_paths.extend(std::env::split_paths(path));
+        }
+        pkg_config_paths.extend(self.all_pkg_config_directories(context));
+        if let Ok(pkg_config_path) = std::env::join_paths(&pkg_config_paths) {
+            command.env("PKG_CONFIG_PATH", pkg_config_path);
+        }
+
+        if context.is_mac() {
+            command.env("MACOSX_DEPLOYMENT_TARGET", context.macos_target_version());
+        }
+
+        for (k, v) in self.all_native_library_vars(context) {
+            command.env(k, v);
+        }
+
+        for (k, v) in &step.env {
+            command.env(k, self.expand(v, context));
+        }
+
+        command
+    }
+}
+
+#[typetag::serde]
+impl Library for ScriptLibrary {
+    fn location(&self) -> &LibraryLocation {
+        &self.source_location
+    }
+
+    fn release_location(&self) -> &LibraryLocation {
+        self.release_location
+            .as_ref()
+            .unwrap_or(&self.source_location)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compiled_library_name(&self) -> CompiledLibraryName {
+        self.compiled_name.clone()
+    }
+
+    fn exported_name(&self) -> &str {
+        self.exported_name.as_deref().unwrap_or_else(|| self.name())
+    }
+
+    fn dependencies(&self) -> Option<&LibraryDependencies> {
+        Some(&self.dependencies)
+    }
+
+    fn options(&self) -> &LibraryOptions {
+        &self.options
+    }
+
+    fn options_mut(&mut self) -> &mut LibraryOptions {
+        &mut self.options
+    }
+
+    fn force_compile(&self, context: &LibraryCompilationContext) -> Result<(), Box<dyn Error>> {
+        let out_dir = self.native_library_prefix(context);
+        if !out_dir.exists() {
+            std::fs::create_dir_all(&out_dir)?;
+        }
+
+        for (index, step) in self.steps.iter().enumerate() {
+            let mut command = self.step_command(step, context);
+            println!("{:?}", &command);
+
+            let status = command.status().map_err(|error| {
+                UserFacingError::new("Failed to build project")
+                    .reason(format!(
+                        "Could not run step {} `{}` of {}: {}",
+                        index + 1,
+                        step,
+                        self.name(),
+                        error
+                    ))
+                    .help("Make sure the program exists and is in the PATH")
+            })?;
+
+            if !status.success() {
+                return Err(Box::new(
+                    UserFacingError::new("Failed to build project")
+                        .reason(format!(
+                            "Step {} `{}` of {} failed with {}",
+                            index + 1,
+                            step,
+                            self.name(),
+                            status
+                        ))
+                        .help("Inspect the output above for the failing command"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compiled_library_directories(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let prefix = self.native_library_prefix(context);
+        self.library_directories
+            .iter()
+            .map(|directory| prefix.join(directory))
+            .collect()
+    }
+
+    fn ensure_requirements(&self, _context: &LibraryCompilationContext) {}
+
+    fn native_library_prefix(&self, context: &LibraryCompilationContext) -> PathBuf {
+        context.build_root().join(self.name())
+    }
+
+    fn native_library_include_headers(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        let prefix = self.native_library_prefix(context);
+        self.header_directories
+            .iter()
+            .map(|directory| prefix.join(directory))
+            .filter(|directory| directory.exists())
+            .collect()
+    }
+
+    fn native_library_linker_libraries(&self, context: &LibraryCompilationContext) -> Vec<PathBuf> {
+        self.compiled_library_directories(context)
+            .into_iter()
+            .filter(|directory| directory.exists())
+            .collect()
+    }
+
+    fn pkg_config_directory(&self, context: &LibraryCompilationContext) -> Option<PathBuf> {
+        self.pkg_config_directory
+            .as_ref()
+            .map(|directory| self.native_library_prefix(context).join(directory))
+            .filter(|directory| directory.exists())
+    }
+
+    fn clone_library(&self) -> Box<dyn Library> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+impl From<ScriptLibrary> for Box<dyn Library> {
+    fn from(library: ScriptLibrary) -> Self {
+        Box::new(library)
+    }
+}
@@ -0,0 +1,62 @@
+#![cfg(unix)]
+
+use shared_library_builder::{
+    AutotoolsLibrary, Library, LibraryCompilationContext, LibraryLocation, LibraryTarget,
+    PathLocation,
+};
+use std::error::Error;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CONFIGURE: &str = r#"#!/bin/sh
+echo "$@" > configure.args
+for arg in "$@"; do
+  case "$arg" in
+    --prefix=*) prefix="${arg#--prefix=}" ;;
+  esac
+done
+printf 'all:\n\ttouch libautotools_fake.a\ninstall:\n\tmkdir -p %s/lib\n\tcp libautotools_fake.a %s/lib/\n' "$prefix" "$prefix" > Makefile
+"#;
+
+#[test]
+fn configure_make_and_install_static_autotools_library() -> Result<(), Box<dyn Error>> {
+    let test_root = std::env::temp_dir().join(format!(
+        "shared-library-builder-autotools-test-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+    let source_root = test_root.join("src");
+    let library_source = source_root.join("autotools_fake");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&library_source)?;
+    fs::create_dir_all(&build_root)?;
+    fs::write(library_source.join("configure"), CONFIGURE)?;
+
+    let context = LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    );
+    let mut library = AutotoolsLibrary::new(
+        "autotools_fake",
+        LibraryLocation::Path(PathLocation::new(library_source)),
+    )
+    .configure_flag("--without-docs");
+    library.be_static();
+
+    let prefix = context.build_root().join("autotools_fake");
+    let compiled_path = library.compile(&context)?;
+
+    assert_eq!(compiled_path, prefix.join("lib").join("libautotools_fake.a"));
+
+    let configure_args = fs::read_to_string(prefix.join("build").join("configure.args"))?;
+    assert!(configure_args.contains(&format!("--prefix={}", prefix.display())));
+    assert!(configure_args.contains(&format!("--host={}", context.target())));
+    assert!(configure_args.contains("--enable-static"));
+    assert!(configure_args.contains("--disable-shared"));
+    assert!(configure_args.contains("--without-docs"));
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
@@ -0,0 +1,619 @@
+#![cfg(feature = "git-location")]
+
+use shared_library_builder::{
+    CompiledLibraryName, GitCleanPolicy, GitLocation, GitRepository, GitSubmodules, Library,
+    LibraryCompilationContext, LibraryLocation, LibraryOrigin, LibraryPatch, LibraryTarget,
+    LockMode, Lockfile, ScriptLibrary, ScriptStep, UrlMirror,
+};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn test_root(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(std::env::temp_dir().join(format!(
+        "shared-library-builder-{}-test-{}-{}",
+        name,
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    )))
+}
+
+fn git(directory: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git")
+        .current_dir(directory)
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .output()?;
+    assert!(output.status.success(), "git {:?} failed", args);
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+/// Create a bare repository with a tagged commit and return its path
+fn bare_repository(root: &Path, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let work = root.join(format!("{}-work", name));
+    let bare = root.join(format!("{}.git", name));
+    fs::create_dir_all(work.join("library"))?;
+    fs::create_dir_all(work.join("docs"))?;
+
+    git(&work, &["init"])?;
+    fs::write(work.join("version.txt"), "1")?;
+    fs::write(work.join("library").join("library.c"), "int library(void);")?;
+    fs::write(work.join("docs").join("manual.txt"), "manual")?;
+    git(&work, &["add", "."])?;
+    git(&work, &["commit", "-m", "version 1"])?;
+    git(&work, &["tag", "v1"])?;
+    fs::write(work.join("version.txt"), "2")?;
+    git(&work, &["commit", "-am", "version 2"])?;
+    git(
+        root,
+        &[
+            "clone",
+            "--bare",
+            &work.to_string_lossy(),
+            &bare.to_string_lossy(),
+        ],
+    )?;
+
+    Ok(bare)
+}
+
+#[test]
+fn derive_repository_name_from_url() {
+    assert_eq!(
+        GitRepository::url("https://gitea.local/mirrors/zlib.git").repository_name(),
+        "zlib"
+    );
+    assert_eq!(
+        GitRepository::url("ssh://git@gitea.local:2222/mirrors/freetype/").repository_name(),
+        "freetype"
+    );
+    assert_eq!(
+        GitRepository::url("git@gitea.local:mirrors/harfbuzz.git").repository_name(),
+        "harfbuzz"
+    );
+    assert_eq!(
+        GitRepository::url("file:///srv/git/pixman.git").repository_name(),
+        "pixman"
+    );
+    assert_eq!(
+        GitRepository::url("git@gitea.local:mirrors/harfbuzz.git")
+            .as_url()
+            .as_str(),
+        "ssh://git@gitea.local/mirrors/harfbuzz.git"
+    );
+    // relative paths are not urls, they are reported as an error instead of panicking
+    assert!(GitRepository::url("mirrors/pixman").try_as_url().is_err());
+}
+
+#[test]
+fn clone_tag_from_local_bare_repository() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("git-url")?;
+    let source_root = test_root.join("src");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&source_root)?;
+    fs::create_dir_all(&build_root)?;
+
+    let bare = bare_repository(&test_root, "local_library")?;
+    let context = LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    );
+
+    let url = format!("file://{}", bare.display());
+    let location = LibraryLocation::Git(GitLocation::url(url).tag("v1"));
+    let default_directory = Path::new("local_library");
+
+    location.ensure_sources(default_directory, &context)?;
+
+    let sources = location.sources_directory(default_directory, &context);
+    assert_eq!(sources, context.sources_root().join("local_library"));
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "1");
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+fn compilation_context(test_root: &Path) -> Result<LibraryCompilationContext, Box<dyn Error>> {
+    let source_root = test_root.join("src");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&source_root)?;
+    fs::create_dir_all(&build_root)?;
+
+    Ok(LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    ))
+}
+
+#[test]
+fn shallow_clone_of_tag_and_commit() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("git-shallow")?;
+    let context = compilation_context(&test_root)?;
+    let bare = bare_repository(&test_root, "shallow_library")?;
+    let first_commit = git(&bare, &["rev-parse", "v1"])?;
+    let url = format!("file://{}", bare.display());
+    let default_directory = Path::new("shallow_library");
+
+    let location = LibraryLocation::Git(GitLocation::url(&url).tag("v1").shallow().blobless());
+    location.ensure_sources(default_directory, &context)?;
+
+    let sources = location.sources_directory(default_directory, &context);
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "1");
+    assert_eq!(git(&sources, &["rev-list", "--count", "HEAD"])?, "1");
+
+    // switching the version of an existing shallow clone only fetches that commit
+    let location = LibraryLocation::Git(GitLocation::url(&url).commit(&first_commit).shallow());
+    location.ensure_sources(default_directory, &context)?;
+    assert_eq!(git(&sources, &["rev-parse", "HEAD"])?, first_commit);
+
+    let location = LibraryLocation::Git(GitLocation::url(&url).shallow());
+    location.ensure_sources(default_directory, &context)?;
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "2");
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn sparse_checkout_of_subdirectory() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("git-sparse")?;
+    let context = compilation_context(&test_root)?;
+    let bare = bare_repository(&test_root, "sparse_library")?;
+    let url = format!("file://{}", bare.display());
+    let default_directory = Path::new("sparse_library");
+
+    let location = LibraryLocation::Git(
+        GitLocation::url(url)
+            .tag("v1")
+            .blobless()
+            .sparse(vec!["library"]),
+    );
+    location.ensure_sources(default_directory, &context)?;
+
+    let sources = location.sources_directory(default_directory, &context);
+    assert!(sources.join("library").join("library.c").exists());
+    assert!(sources.join("version.txt").exists());
+    assert!(!sources.join("docs").exists());
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn sparse_checkout_of_latest_version() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("git-sparse-latest")?;
+    let context = compilation_context(&test_root)?;
+    let bare = bare_repository(&test_root, "sparse_library")?;
+    let url = format!("file://{}", bare.display());
+    let default_directory = Path::new("sparse_library");
+
+    let location = LibraryLocation::Git(GitLocation::url(url).sparse(vec!["library"]));
+    location.ensure_sources(default_directory, &context)?;
+
+    let sources = location.sources_directory(default_directory, &context);
+    assert!(sources.join("library").join("library.c").exists());
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "2");
+    assert!(!sources.join("docs").exists());
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn sparse_checkout_is_not_dirty() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("git-sparse-dirty")?;
+    let context = compilation_context(&test_root)?;
+    let bare = bare_repository(&test_root, "sparse_library")?;
+    let url = format!("file://{}", bare.display());
+    let default_directory = Path::new("sparse_library");
+
+    // files outside of the sparse set are not local modifications, neither right after cloning
+    // nor once the working tree is checked out
+    for _ in 0..2 {
+        let location = LibraryLocation::Git(
+            GitLocation::url(&url)
+                .tag("v1")
+                .blobless()
+                .sparse(vec!["library"])
+                .clean_policy(GitCleanPolicy::FailIfDirty),
+        );
+        location.ensure_sources(default_directory, &context)?;
+    }
+
+    let location = LibraryLocation::Git(
+        GitLocation::url(&url)
+            .sparse(vec!["library"])
+            .clean_policy(GitCleanPolicy::FailIfDirty),
+    );
+    let sources = location.sources_directory(default_directory, &context);
+    fs::write(sources.join("library").join("library.c"), "modified")?;
+    let error = location
+        .ensure_sources(default_directory, &context)
+        .unwrap_err();
+    let message = format!("{:?}", error);
+    assert!(
+        message.contains("local modifications: library/library.c\""),
+        "{}",
+        message
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn update_submodules_after_checkout() -> Result<(), Box<dyn Error>> {
+    // git refuses to clone submodules over the file protocol by default. The builder runs git itself,
+    // so the test runs in its own process with the protocol allowed in its environment,
+    // instead of changing the environment of the other tests that run in parallel
+    if std::env::var("GIT_CONFIG_KEY_0").as_deref() != Ok("protocol.file.allow") {
+        let output = Command::new(std::env::current_exe()?)
+            .args(["update_submodules_after_checkout", "--exact"])
+            .env("GIT_CONFIG_COUNT", "1")
+            .env("GIT_CONFIG_KEY_0", "protocol.file.allow")
+            .env("GIT_CONFIG_VALUE_0", "always")
+            .output()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}", stdout);
+        assert!(stdout.contains("1 passed"), "{}", stdout);
+        return Ok(());
+    }
+
+    let test_root = test_root("git-submodules")?;
+    let context = compilation_context(&test_root)?;
+    let vendored = bare_repository(&test_root, "vendored")?;
+    let bare = bare_repository(&test_root, "submodule_library")?;
+
+    let work = test_root.join("submodule_library-work");
+    git(
+        &work,
+        &[
+            "submodule",
+            "add",
+            &format!("file://{}", vendored.display()),
+            "third_party/vendored",
+        ],
+    )?;
+    git(&work, &["commit", "-m", "add submodule"])?;
+    git(&work, &["push", &bare.to_string_lossy(), "HEAD"])?;
+
+    let url = format!("file://{}", bare.display());
+    let default_directory = Path::new("submodule_library");
+
+    let location = LibraryLocation::Git(GitLocation::url(&url));
+    location.ensure_sources(default_directory, &context)?;
+    let sources = location.sources_directory(default_directory, &context);
+    let vendored_sources = sources.join("third_party").join("vendored");
+    assert!(!vendored_sources.join("version.txt").exists());
+
+    let location = LibraryLocation::Git(
+        GitLocation::url(&url).submodules(GitSubmodules::paths(vec!["third_party/vendored"])),
+    );
+    location.ensure_sources(default_directory, &context)?;
+    assert_eq!(
+        fs::read_to_string(vendored_sources.join("version.txt"))?,
+        "2"
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn keep_local_work_in_sources_directory() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("git-dirty")?;
+    let context = compilation_context(&test_root)?;
+    let bare = bare_repository(&test_root, "dirty_library")?;
+    let url = format!("file://{}", bare.display());
+    let default_directory = Path::new("dirty_library");
+
+    let location = LibraryLocation::Git(GitLocation::url(&url));
+    location.ensure_sources(default_directory, &context)?;
+    let sources = location.sources_directory(default_directory, &context);
+
+    // untracked files survive by default
+    fs::write(sources.join("local.patch"), "patch")?;
+    location.ensure_sources(default_directory, &context)?;
+    assert!(sources.join("local.patch").exists());
+
+    fs::write(sources.join("version.txt"), "modified")?;
+    let location =
+        LibraryLocation::Git(GitLocation::url(&url).clean_policy(GitCleanPolicy::FailIfDirty));
+    assert!(location
+        .ensure_sources(default_directory, &context)
+        .is_err());
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "modified");
+
+    let location = LibraryLocation::Git(GitLocation::url(&url).clean_policy(GitCleanPolicy::Clean));
+    git(&sources, &["checkout", "--", "version.txt"])?;
+    location.ensure_sources(default_directory, &context)?;
+    assert!(!sources.join("local.patch").exists());
+
+    // a local commit can not be fast-forwarded to the latest version
+    let work = test_root.join("dirty_library-work");
+    fs::write(work.join("version.txt"), "3")?;
+    git(&work, &["commit", "-am", "version 3"])?;
+    git(&work, &["push", &bare.to_string_lossy(), "HEAD"])?;
+
+    fs::write(sources.join("version.txt"), "local")?;
+    git(&sources, &["commit", "-am", "local version"])?;
+    let location = LibraryLocation::Git(GitLocation::url(&url));
+    let update_context = context.clone().with_lock_mode(LockMode::Update);
+    let error = location
+        .ensure_sources(default_directory, &update_context)
+        .unwrap_err();
+    assert!(format!("{:?}", error).contains("local commits"));
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "local");
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn record_and_pin_resolved_revisions() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("git-lock")?;
+    let context = compilation_context(&test_root)?;
+    let bare = bare_repository(&test_root, "locked_library")?;
+    let second_commit = git(&bare, &["rev-parse", "HEAD"])?;
+    let url = format!("file://{}", bare.display());
+    let default_directory = Path::new("locked_library");
+
+    let git_location = GitLocation::url(&url);
+    let location = LibraryLocation::Git(git_location.clone());
+
+    // nothing is locked yet
+    let locked_context = context.clone().with_lock_mode(LockMode::Locked);
+    assert!(location
+        .ensure_sources(default_directory, &locked_context)
+        .is_err());
+
+    location.ensure_sources(default_directory, &context)?;
+    assert_eq!(
+        git_location.resolved_revision(default_directory, &context),
+        Some(second_commit.clone())
+    );
+    let lockfile = Lockfile::read(Lockfile::path(&context))?;
+    let locked = lockfile.revision("locked_library").unwrap();
+    assert_eq!(locked.repository, url);
+    assert_eq!(locked.version, "latest");
+    assert_eq!(locked.revision, second_commit);
+
+    let work = test_root.join("locked_library-work");
+    fs::write(work.join("version.txt"), "3")?;
+    git(&work, &["commit", "-am", "version 3"])?;
+    git(&work, &["push", &bare.to_string_lossy(), "HEAD"])?;
+    let third_commit = git(&bare, &["rev-parse", "HEAD"])?;
+
+    // the latest version moves on, but the recorded revision is checked out until updated
+    location.ensure_sources(default_directory, &context)?;
+    let sources = location.sources_directory(default_directory, &context);
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "2");
+    let lockfile = Lockfile::read(Lockfile::path(&context))?;
+    assert_eq!(
+        lockfile.revision("locked_library").unwrap().revision,
+        second_commit
+    );
+
+    location.ensure_sources(default_directory, &locked_context)?;
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "2");
+
+    let update_context = context.clone().with_lock_mode(LockMode::Update);
+    location.ensure_sources(default_directory, &update_context)?;
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "3");
+    let lockfile = Lockfile::read(Lockfile::path(&context))?;
+    assert_eq!(
+        lockfile.revision("locked_library").unwrap().revision,
+        third_commit
+    );
+
+    // a revision is only locked for the version it is resolved from
+    let location = LibraryLocation::Git(git_location.tag("v1"));
+    location.ensure_sources(default_directory, &context)?;
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "1");
+    let lockfile = Lockfile::read(Lockfile::path(&context))?;
+    assert_eq!(
+        lockfile.revision("locked_library").unwrap().version,
+        "tag v1"
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn revert_patches_before_checkout() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("git-patch")?;
+    let context = compilation_context(&test_root)?;
+    let bare = bare_repository(&test_root, "patched_library")?;
+    let url = format!("file://{}", bare.display());
+    let default_directory = Path::new("patched_library");
+
+    let patch = LibraryPatch::inline(
+        "version",
+        "--- a/version.txt\n+++ b/version.txt\n@@ -1 +1 @@\n-1\n\\ No newline at end of file\n+patched\n\\ No newline at end of file\n",
+    );
+    let location = LibraryLocation::Git(
+        GitLocation::url(&url)
+            .tag("v1")
+            .clean_policy(GitCleanPolicy::FailIfDirty)
+            .patch(patch),
+    );
+    location.ensure_sources(default_directory, &context)?;
+    let sources = location.sources_directory(default_directory, &context);
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "patched");
+
+    // our own patches do not make the sources dirty
+    location.ensure_sources(default_directory, &context)?;
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "patched");
+
+    let branch = git(&bare, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    let location = LibraryLocation::Git(
+        GitLocation::url(&url)
+            .branch(branch)
+            .clean_policy(GitCleanPolicy::FailIfDirty),
+    );
+    location.ensure_sources(default_directory, &context)?;
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "2");
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn checkout_local_revisions_offline() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("git-offline")?;
+    let context = compilation_context(&test_root)?;
+    let offline_context = context.clone().with_offline(true);
+    let bare = bare_repository(&test_root, "offline_library")?;
+    let url = format!("file://{}", bare.display());
+    let default_directory = Path::new("offline_library");
+
+    let location = LibraryLocation::Git(GitLocation::url(&url).tag("v1"));
+    assert!(location
+        .unavailable_offline(default_directory, &offline_context)
+        .is_some());
+    assert!(location
+        .ensure_sources(default_directory, &offline_context)
+        .is_err());
+
+    location.ensure_sources(default_directory, &context)?;
+    let sources = location.sources_directory(default_directory, &context);
+
+    // a new tag on the remote is not fetched without network access
+    let work = test_root.join("offline_library-work");
+    git(&work, &["tag", "v2"])?;
+    git(&work, &["push", &bare.to_string_lossy(), "v2"])?;
+
+    let location = LibraryLocation::Git(GitLocation::url(&url).tag("v2"));
+    let unavailable = location
+        .unavailable_offline(default_directory, &offline_context)
+        .unwrap();
+    assert!(unavailable.contains("v2"), "{}", unavailable);
+    assert!(location
+        .ensure_sources(default_directory, &offline_context)
+        .is_err());
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "1");
+
+    let location = LibraryLocation::Git(GitLocation::url(&url));
+    assert!(location
+        .unavailable_offline(default_directory, &offline_context)
+        .is_none());
+    location.ensure_sources(default_directory, &offline_context)?;
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "1");
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn clone_and_fetch_from_mirrors() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("git-mirror")?;
+    let bare = bare_repository(&test_root, "mirrored_library")?;
+    let mirror = format!("file://{}/", test_root.display());
+
+    let rule: UrlMirror = format!(
+        "https://github.com/feenkcom/=file:///unreachable/,{}",
+        mirror
+    )
+    .parse()?;
+    assert_eq!(rule.mirrors().len(), 2);
+    assert!("https://github.com/".parse::<UrlMirror>().is_err());
+
+    let context = compilation_context(&test_root)?
+        .with_mirror(UrlMirror::new(
+            "https://github.com/",
+            "file:///unreachable/github/",
+        ))
+        .with_mirror(rule);
+    assert_eq!(
+        context.mirrored_urls("https://github.com/feenkcom/mirrored_library.git"),
+        vec![
+            "file:///unreachable/mirrored_library.git".to_string(),
+            format!("{}mirrored_library.git", mirror)
+        ]
+    );
+    assert_eq!(
+        context.mirrored_urls("https://gitlab.com/feenkcom/library"),
+        vec!["https://gitlab.com/feenkcom/library".to_string()]
+    );
+
+    let default_directory = Path::new("mirrored_library");
+    let location =
+        LibraryLocation::Git(GitLocation::github("feenkcom", "mirrored_library").tag("v1"));
+    location.ensure_sources(default_directory, &context)?;
+    let sources = location.sources_directory(default_directory, &context);
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "1");
+
+    // new tags are fetched from the mirror as well
+    let work = test_root.join("mirrored_library-work");
+    git(&work, &["tag", "v2"])?;
+    git(&work, &["push", &bare.to_string_lossy(), "v2"])?;
+    let location =
+        LibraryLocation::Git(GitLocation::github("feenkcom", "mirrored_library").tag("v2"));
+    location.ensure_sources(default_directory, &context)?;
+    assert_eq!(fs::read_to_string(sources.join("version.txt"))?, "2");
+
+    // the lockfile records the original repository, not the mirror
+    let lockfile = Lockfile::read(Lockfile::path(&context))?;
+    assert_eq!(
+        lockfile.revision("mirrored_library").unwrap().repository,
+        "https://github.com/feenkcom/mirrored_library.git"
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn record_the_revision_of_compiled_libraries() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("git-recompile")?;
+    let context = compilation_context(&test_root)?;
+    let bare = bare_repository(&test_root, "compiled_library")?;
+    let url = format!("file://{}", bare.display());
+
+    let file_name =
+        CompiledLibraryName::Default.file_name("compiled_library", context.target(), false);
+    let copy_version = format!(
+        "mkdir -p {{prefix}}/lib && cp {{source}}/version.txt {{prefix}}/lib/{}",
+        file_name
+    );
+    let library = ScriptLibrary::new(
+        "compiled_library",
+        LibraryLocation::Git(GitLocation::url(&url)),
+    )
+    .step(ScriptStep::new("sh").args(vec!["-c", copy_version.as_str()]));
+
+    let compiled_library = library.compile(&context)?;
+    assert_eq!(fs::read_to_string(&compiled_library)?, "2");
+    let first_commit = git(&bare, &["rev-parse", "HEAD"])?;
+    assert_eq!(
+        LibraryOrigin::read(&compiled_library),
+        Some(LibraryOrigin::compiled(
+            library.location().fingerprint()?,
+            Some(first_commit)
+        ))
+    );
+
+    // a new commit of the latest version is compiled and recorded once the lock is updated
+    let work = test_root.join("compiled_library-work");
+    fs::write(work.join("version.txt"), "3")?;
+    git(&work, &["commit", "-am", "version 3"])?;
+    git(&work, &["push", &bare.to_string_lossy(), "HEAD"])?;
+
+    let context = context.with_lock_mode(LockMode::Update);
+    assert_eq!(fs::read_to_string(library.compile(&context)?)?, "3");
+    assert_eq!(
+        LibraryOrigin::read(&compiled_library),
+        Some(library.compiled_origin(&context)?)
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use shared_library_builder::{
+    HeaderOnlyLibrary, Library, LibraryCompilationContext, LibraryDependencies, LibraryLocation,
+    LibraryTarget, PathLocation,
+};
+use std::error::Error;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[test]
+fn install_selected_headers_and_expose_them_to_dependents() -> Result<(), Box<dyn Error>> {
+    let test_root = std::env::temp_dir().join(format!(
+        "shared-library-builder-header-only-test-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+    let source_root = test_root.join("src");
+    let library_source = source_root.join("headers_fake");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(library_source.join("glm").join("detail"))?;
+    fs::create_dir_all(&build_root)?;
+    fs::write(library_source.join("glm").join("glm.hpp"), b"#pragma once")?;
+    fs::write(
+        library_source.join("glm").join("detail").join("setup.hpp"),
+        b"#pragma once",
+    )?;
+    fs::write(library_source.join("stb_image.h"), b"#pragma once")?;
+    fs::write(library_source.join("README.md"), b"not a header")?;
+
+    let context = LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    );
+    let library = HeaderOnlyLibrary::new(
+        "headers_fake",
+        LibraryLocation::Path(PathLocation::new(library_source)),
+    )
+    .with_headers("glm")
+    .with_headers_into("stb_image.h", "stb/stb_image.h");
+
+    let prefix = context.build_root().join("headers_fake");
+    let include = prefix.join("include");
+
+    assert_eq!(library.compile(&context)?, prefix);
+    assert!(library.is_compiled(&context));
+    assert!(include.join("glm").join("detail").join("setup.hpp").exists());
+    assert!(include.join("stb").join("stb_image.h").exists());
+    assert!(!include.join("README.md").exists());
+
+    let dependencies = LibraryDependencies::new().push(library.into());
+    assert_eq!(dependencies.include_headers(&context), vec![include]);
+    assert_eq!(dependencies.dependency_prefixes(&context), vec![prefix]);
+    assert!(dependencies.linker_libraries(&context).is_empty());
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use shared_library_builder::{
+    LibraryCompilationContext, LibraryLocation, LibraryPatch, LibraryTarget, PathLocation,
+};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn test_root(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(std::env::temp_dir().join(format!(
+        "shared-library-builder-{}-test-{}-{}",
+        name,
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    )))
+}
+
+const API_LEVEL_PATCH: &str = "\
+--- a/config.h
++++ b/config.h
+@@ -1,2 +1,2 @@
+ #define LIBRARY 1
+-#define ANDROID_API 21
++#define ANDROID_API 30
+";
+
+const WINDOWS_PATCH: &str = "\
+--- a/config.h
++++ b/config.h
+@@ -1,2 +1,3 @@
+ #define LIBRARY 1
++#define WINDOWS_EXPORT __declspec(dllexport)
+ #define ANDROID_API 30
+";
+
+const CONFLICTING_PATCH: &str = "\
+--- a/config.h
++++ b/config.h
+@@ -1,2 +1,2 @@
+ #define LIBRARY 1
+-#define ANDROID_API 19
++#define ANDROID_API 33
+";
+
+#[test]
+fn apply_patches_once_in_order() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("patch")?;
+    let sources = test_root.join("original");
+    fs::create_dir_all(&sources)?;
+    fs::write(
+        sources.join("config.h"),
+        "#define LIBRARY 1\n#define ANDROID_API 21\n",
+    )?;
+    let windows_patch = test_root.join("windows.patch");
+    fs::write(&windows_patch, WINDOWS_PATCH)?;
+    fs::create_dir_all(test_root.join("src"))?;
+    fs::create_dir_all(test_root.join("build"))?;
+
+    let context = LibraryCompilationContext::new(
+        test_root.join("src"),
+        test_root.join("build"),
+        LibraryTarget::for_current_platform(),
+        false,
+    );
+    let default_directory = Path::new("library");
+
+    let location = LibraryLocation::Path(
+        PathLocation::new(&sources)
+            .patch(LibraryPatch::inline("android-api", API_LEVEL_PATCH))
+            .patch(LibraryPatch::file(&windows_patch)),
+    );
+
+    let expected =
+        "#define LIBRARY 1\n#define WINDOWS_EXPORT __declspec(dllexport)\n#define ANDROID_API 30\n";
+    location.ensure_sources(default_directory, &context)?;
+    // patches are applied to a copy of the sources, the original sources are not modified
+    let patched = location.sources_directory(default_directory, &context);
+    assert_eq!(patched, context.sources_root().join("library"));
+    assert_eq!(fs::read_to_string(patched.join("config.h"))?, expected);
+    assert_eq!(
+        fs::read_to_string(sources.join("config.h"))?,
+        "#define LIBRARY 1\n#define ANDROID_API 21\n"
+    );
+
+    // applied patches are recorded and not applied twice
+    location.ensure_sources(default_directory, &context)?;
+    assert_eq!(fs::read_to_string(patched.join("config.h"))?, expected);
+
+    // removing a patch reverts it
+    let location = LibraryLocation::Path(
+        PathLocation::new(&sources).patch(LibraryPatch::inline("android-api", API_LEVEL_PATCH)),
+    );
+    location.ensure_sources(default_directory, &context)?;
+    assert_eq!(
+        fs::read_to_string(patched.join("config.h"))?,
+        "#define LIBRARY 1\n#define ANDROID_API 30\n"
+    );
+
+    // patches are part of the fingerprint
+    let unpatched = LibraryLocation::Path(PathLocation::new(&sources));
+    assert_ne!(location.fingerprint()?, unpatched.fingerprint()?);
+    assert_eq!(location.fingerprint()?, location.clone().fingerprint()?);
+
+    let location = LibraryLocation::Path(
+        PathLocation::new(&sources)
+            .patch(LibraryPatch::inline("android-api", API_LEVEL_PATCH))
+            .patch(LibraryPatch::inline("conflicting-api", CONFLICTING_PATCH)),
+    );
+    let error = location
+        .ensure_sources(default_directory, &context)
+        .unwrap_err();
+    let message = format!("{:?}", error);
+    assert!(message.contains("conflicting-api"), "{}", message);
+    assert!(message.contains("config.h:1"), "{}", message);
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use shared_library_builder::{
+    LibraryCompilationContext, LibraryLocation, LibraryTarget, MesonLibrary, PathLocation,
+};
+use std::error::Error;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[test]
+fn generate_cross_file_for_target() -> Result<(), Box<dyn Error>> {
+    let test_root = std::env::temp_dir().join(format!(
+        "shared-library-builder-meson-test-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+    let source_root = test_root.join("src");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&source_root)?;
+    fs::create_dir_all(&build_root)?;
+
+    let context = LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::AArch64appleDarwin,
+        false,
+    );
+    let library = MesonLibrary::new(
+        "meson_fake",
+        LibraryLocation::Path(PathLocation::new(source_root.join("meson_fake"))),
+    );
+
+    let cross_file = library.cross_file(&context)?;
+
+    assert!(cross_file.contains("c = 'clang'"));
+    assert!(cross_file.contains("c_args = ['-arch', 'arm64', '-mmacosx-version-min="));
+    assert!(cross_file.contains("system = 'darwin'"));
+    assert!(cross_file.contains("cpu_family = 'aarch64'"));
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use shared_library_builder::{
+    CompiledLibraryName, Library, LibraryCompilationContext, LibraryLocation, LibraryOrigin,
+    LibraryTarget, PathLocation, PrebuiltOnlyLibrary,
+};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn test_root(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(std::env::temp_dir().join(format!(
+        "shared-library-builder-{}-test-{}-{}",
+        name,
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    )))
+}
+
+#[test]
+fn unpack_prebuilt_only_library_into_prefix() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("prebuilt-only")?;
+    let source_root = test_root.join("src");
+    let build_root = test_root.join("build");
+    let distribution = test_root.join("vendor-sdk");
+    fs::create_dir_all(&source_root)?;
+    fs::create_dir_all(&build_root)?;
+
+    let target = LibraryTarget::for_current_platform();
+    let library_file = CompiledLibraryName::Default.file_name("vendor", &target, true);
+    fs::create_dir_all(distribution.join("lib").join("pkgconfig"))?;
+    fs::create_dir_all(distribution.join("include"))?;
+    fs::write(distribution.join("lib").join(&library_file), b"vendor library")?;
+    fs::write(distribution.join("include").join("vendor.h"), b"void vendor(void);")?;
+    fs::write(
+        distribution.join("lib").join("pkgconfig").join("vendor.pc"),
+        "prefix=/opt/vendor\nlibdir=${prefix}/lib\n",
+    )?;
+
+    let context = LibraryCompilationContext::new(&source_root, &build_root, target, false);
+    let mut library = PrebuiltOnlyLibrary::new(
+        "vendor",
+        target,
+        LibraryLocation::Path(PathLocation::new(&distribution)),
+    );
+    library.be_static();
+
+    let prefix = context.build_root().join("vendor");
+    let compiled_path = library.compile(&context)?;
+
+    assert_eq!(compiled_path, prefix.join("lib").join(&library_file));
+    assert_eq!(
+        library.native_library_include_headers(&context),
+        vec![prefix.join("include")]
+    );
+    assert_eq!(
+        fs::read_to_string(prefix.join("lib").join("pkgconfig").join("vendor.pc"))?,
+        format!("prefix={}\nlibdir=${{prefix}}/lib\n", prefix.display())
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn fail_when_there_is_no_prebuilt_library_for_target() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("prebuilt-only-missing")?;
+    let source_root = test_root.join("src");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&source_root)?;
+    fs::create_dir_all(&build_root)?;
+
+    let context = LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    );
+    let library = PrebuiltOnlyLibrary::new(
+        "vendor",
+        LibraryTarget::AArch64LinuxAndroid,
+        LibraryLocation::Path(PathLocation::new(test_root.join("vendor-sdk"))),
+    );
+
+    let error = library.compile(&context).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains(&format!("There is no prebuilt vendor for {}", context.target())));
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn refuse_to_deserialize_without_artifacts() -> Result<(), Box<dyn Error>> {
+    let library: Box<dyn Library> = Box::new(PrebuiltOnlyLibrary::new(
+        "vendor",
+        LibraryTarget::for_current_platform(),
+        LibraryLocation::Path(PathLocation::new("vendor-sdk")),
+    ));
+    let serialized = serde_json::to_value(&library)?;
+    assert!(serde_json::from_value::<Box<dyn Library>>(serialized.clone()).is_ok());
+
+    let mut serialized = serialized;
+    serialized["artifacts"] = serde_json::json!([]);
+    let error = serde_json::from_value::<Box<dyn Library>>(serialized).unwrap_err();
+    assert!(
+        error.to_string().contains("at least one artifact"),
+        "{}",
+        error
+    );
+    Ok(())
+}
+
+#[test]
+fn unpack_the_prebuilt_library_of_the_context_target() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("prebuilt-only-target")?;
+    let source_root = test_root.join("src");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&source_root)?;
+    fs::create_dir_all(&build_root)?;
+
+    let current = LibraryTarget::for_current_platform();
+    let target = if current == LibraryTarget::AArch64LinuxAndroid {
+        LibraryTarget::X8664UnknownlinuxGNU
+    } else {
+        LibraryTarget::AArch64LinuxAndroid
+    };
+    let distribution = |target: &LibraryTarget| -> Result<PathBuf, Box<dyn Error>> {
+        let distribution = test_root.join(format!("vendor-sdk-{}", target));
+        let library_file = CompiledLibraryName::Default.file_name("vendor", target, true);
+        fs::create_dir_all(distribution.join("lib"))?;
+        fs::write(
+            distribution.join("lib").join(library_file),
+            target.to_string(),
+        )?;
+        Ok(distribution)
+    };
+
+    let target_location = LibraryLocation::Path(PathLocation::new(distribution(&target)?));
+    let mut library = PrebuiltOnlyLibrary::new(
+        "vendor",
+        current,
+        LibraryLocation::Path(PathLocation::new(distribution(&current)?)),
+    )
+    .artifact(target, target_location.clone());
+    library.be_static();
+
+    let context = LibraryCompilationContext::new(&source_root, &build_root, target, false);
+    let compiled_path = library.compile(&context)?;
+    assert_eq!(fs::read_to_string(compiled_path)?, target.to_string());
+    assert_eq!(
+        library.compiled_origin(&context)?,
+        LibraryOrigin::compiled(target_location.fingerprint()?, None)
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
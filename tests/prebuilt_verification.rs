@@ -0,0 +1,87 @@
+#![cfg(unix)]
+
+use shared_library_builder::{
+    checksum_asset_name, sha256_file, signature_asset_name, Library, LibraryCompilationContext,
+    LibraryLocation, LibraryTarget, PathLocation, ScriptLibrary, ScriptStep,
+};
+use std::error::Error;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A minisign public key and a signature of "built\n" made with its secret key
+const PUBLIC_KEY: &str = "RWQBAgMEBQYHCOpKbGPinFIKvvVQexMuxfmVR3auvr57kkIe6mkURtIs";
+const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQBAgMEBQYHCJNN26bFL0nZ+BdwUkQT5yW++l7SuqOuqf7gzSUwFgk1SP03+NF0rw+dZs4uALoqsQRgTGLf85uwn0f4ZQ1rmAg=
+trusted comment: timestamp:0\tfile:published
+SFUaJsJaatt77kGe3lZa6W8Q16onI1RxzOmXIqDj53s6ImfupMfWeju50weTVpP6vOLUfH3UqjDVfduTHf+dAw==
+";
+
+#[test]
+fn verify_prebuilt_libraries_before_using_them() -> Result<(), Box<dyn Error>> {
+    let test_root = std::env::temp_dir().join(format!(
+        "shared-library-builder-verify-test-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+    let prebuilt = test_root.join("prebuilt");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&prebuilt)?;
+    fs::create_dir_all(&build_root)?;
+
+    let context = LibraryCompilationContext::new(
+        &test_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    );
+    // the library can not be compiled, so it is only compiled if no prebuilt library is accepted
+    let library = ScriptLibrary::new(
+        "published",
+        LibraryLocation::Path(
+            PathLocation::new(test_root.join("missing")).prebuilt_library_directory(&prebuilt),
+        ),
+    )
+    .step(ScriptStep::new("sh").args(vec!["-c", "exit 1"]));
+
+    let asset_name = library.prebuilt_library_asset_name(&context);
+    let asset = prebuilt.join(&asset_name);
+    let checksum = prebuilt.join(checksum_asset_name(&asset_name));
+    let signature = prebuilt.join(signature_asset_name(&asset_name));
+    let publish = |contents: &str| -> Result<(), Box<dyn Error>> {
+        fs::write(&asset, contents)?;
+        fs::write(
+            &checksum,
+            format!("{}  {}\n", sha256_file(&asset)?, asset_name),
+        )?;
+        Ok(())
+    };
+
+    // an asset that does not match its checksum is not used
+    publish("built\n")?;
+    fs::write(&asset, "tampered\n")?;
+    assert!(library.compile(&context).is_err());
+    assert!(!library.exported_library_path(&context).exists());
+
+    // strict checksums require the companion checksum
+    fs::write(&asset, "built\n")?;
+    fs::remove_file(&checksum)?;
+    assert!(library
+        .compile(&context.clone().with_strict_checksums(true))
+        .is_err());
+
+    // with a public key the asset must have a valid signature
+    publish("built\n")?;
+    let signed_context = context.clone().with_prebuilt_public_key(PUBLIC_KEY);
+    assert!(library.compile(&signed_context).is_err());
+
+    fs::write(&signature, SIGNATURE)?;
+    publish("tampered\n")?;
+    assert!(library.compile(&signed_context).is_err());
+
+    publish("built\n")?;
+    let retrieved = library.compile(&signed_context)?;
+    assert_eq!(fs::read_to_string(retrieved)?, "built\n");
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+#![cfg(unix)]
+
+use shared_library_builder::{
+    checksum_asset_name, publish_prebuilt_library, sha256_file, CompiledLibraryName,
+    DirectoryPublisher, Library, LibraryCompilationContext, LibraryLocation, LibraryOrigin,
+    LibraryTarget, PathLocation, ScriptLibrary, ScriptStep,
+};
+use std::error::Error;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[test]
+fn publish_and_retrieve_prebuilt_libraries() -> Result<(), Box<dyn Error>> {
+    let test_root = std::env::temp_dir().join(format!(
+        "shared-library-builder-publish-test-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+    let library_source = test_root.join("src").join("published");
+    let prebuilt = test_root.join("prebuilt");
+    fs::create_dir_all(&library_source)?;
+
+    let context_in = |build: &str| {
+        let build_root = test_root.join(build);
+        fs::create_dir_all(&build_root)?;
+        Ok::<_, Box<dyn Error>>(LibraryCompilationContext::new(
+            test_root.join("src"),
+            build_root,
+            LibraryTarget::for_current_platform(),
+            false,
+        ))
+    };
+    let library_with = |step: &str| {
+        ScriptLibrary::new(
+            "published",
+            LibraryLocation::Path(
+                PathLocation::new(&library_source).prebuilt_library_directory(&prebuilt),
+            ),
+        )
+        .step(ScriptStep::new("sh").args(vec!["-c", step]))
+    };
+
+    let context = context_in("publisher")?;
+    let file_name = CompiledLibraryName::Default.file_name("published", context.target(), false);
+    let library = library_with(&format!(
+        "mkdir -p {{prefix}}/lib && echo built > {{prefix}}/lib/{}",
+        file_name
+    ));
+    let compiled_library = library.compile(&context)?;
+    assert_eq!(
+        LibraryOrigin::read(&compiled_library),
+        Some(library.compiled_origin(&context)?)
+    );
+    let published = publish_prebuilt_library(
+        &library,
+        &compiled_library,
+        &DirectoryPublisher::new(&prebuilt),
+        &context,
+    )?;
+
+    // the asset is published under the name that retrieval expects, next to its digest
+    let asset_name = library.prebuilt_library_asset_name(&context);
+    let asset = prebuilt.join(&asset_name);
+    let checksum = prebuilt.join(checksum_asset_name(&asset_name));
+    assert_eq!(
+        published,
+        vec![asset.display().to_string(), checksum.display().to_string()]
+    );
+    assert_eq!(
+        fs::read_to_string(&checksum)?,
+        format!("{}  {}\n", sha256_file(&asset)?, asset_name)
+    );
+
+    // a library that can not be compiled is retrieved from the published assets
+    let context = context_in("consumer")?;
+    let library = library_with("exit 1");
+    let retrieved = library.compile(&context)?;
+    assert_eq!(retrieved, library.exported_library_path(&context));
+    assert_eq!(fs::read_to_string(retrieved)?, "built\n");
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[cfg(feature = "downloader")]
+#[test]
+fn remove_assets_staged_for_github_releases() -> Result<(), Box<dyn Error>> {
+    use shared_library_builder::{GitHubReleasePublisher, PrebuiltPublisher};
+    use std::os::unix::fs::PermissionsExt;
+
+    let test_root = std::env::temp_dir().join(format!(
+        "shared-library-builder-publish-github-test-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+
+    // gh is replaced by a script that only accepts uploads to the v1 release.
+    // The test runs in its own process with the script on the PATH,
+    // instead of changing the environment of the other tests that run in parallel
+    if std::env::var_os("GH_STUB").is_none() {
+        let stub_directory = test_root.join("bin");
+        fs::create_dir_all(&stub_directory)?;
+        let stub = stub_directory.join("gh");
+        fs::write(&stub, "#!/bin/sh\ntest -f \"$4\" && test \"$3\" = v1\n")?;
+        fs::set_permissions(&stub, fs::Permissions::from_mode(0o755))?;
+
+        let mut paths = vec![stub_directory];
+        paths.extend(std::env::split_paths(
+            &std::env::var_os("PATH").unwrap_or_default(),
+        ));
+        let output = std::process::Command::new(std::env::current_exe()?)
+            .args(["remove_assets_staged_for_github_releases", "--exact"])
+            .env("PATH", std::env::join_paths(paths)?)
+            .env("GH_STUB", "1")
+            .output()?;
+        fs::remove_dir_all(test_root)?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}", stdout);
+        assert!(stdout.contains("1 passed"), "{}", stdout);
+        return Ok(());
+    }
+
+    fs::create_dir_all(&test_root)?;
+    let file = test_root.join("library");
+    fs::write(&file, "library")?;
+    let staging = std::env::temp_dir().join(format!(
+        "shared-library-builder-publish-{}",
+        std::process::id()
+    ));
+
+    let published =
+        GitHubReleasePublisher::new("feenkcom", "library", "v1").publish(&file, "library.so")?;
+    assert_eq!(
+        published,
+        "https://github.com/feenkcom/library/releases/download/v1/library.so"
+    );
+    assert!(!staging.exists());
+
+    assert!(GitHubReleasePublisher::new("feenkcom", "library", "v2")
+        .publish(&file, "library.so")
+        .is_err());
+    assert!(!staging.exists());
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use shared_library_builder::{
-    Library, LibraryCompilationContext, LibraryLocation, LibraryTarget, PathLocation, RustLibrary,
+    CbindgenConfig, CompiledLibraryName, Library, LibraryCompilationContext, LibraryLocation,
+    LibraryTarget, PathLocation, RustLibrary,
 };
 use std::error::Error;
 use std::fs;
@@ -223,3 +224,222 @@ fn copy_rust_prebuilt_library_to_exported_path() -> Result<(), Box<dyn Error>> {
     fs::remove_dir_all(test_root)?;
     Ok(())
 }
+
+#[test]
+fn expose_cargo_c_installation_prefix_to_dependent_libraries() -> Result<(), Box<dyn Error>> {
+    let test_root = std::env::temp_dir().join(format!(
+        "shared-library-builder-cargo-c-test-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+    let source_root = test_root.join("src");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&source_root)?;
+    fs::create_dir_all(&build_root)?;
+
+    let context = LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    );
+    let library = RustLibrary::new(
+        "rust_c",
+        LibraryLocation::Path(PathLocation::new(source_root.join("rust_c"))),
+    )
+    .cargo_c();
+
+    let prefix = context.build_root().join("rust_c");
+    fs::create_dir_all(prefix.join("include").join("rust_c"))?;
+    fs::create_dir_all(prefix.join("lib").join("pkgconfig"))?;
+
+    assert_eq!(library.native_library_prefix(&context), prefix);
+    assert_eq!(
+        library.native_library_include_headers(&context),
+        vec![prefix.join("include")]
+    );
+    assert_eq!(
+        library.native_library_linker_libraries(&context),
+        vec![prefix.join("lib")]
+    );
+    assert_eq!(
+        library.pkg_config_directory(&context),
+        Some(prefix.join("lib").join("pkgconfig"))
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn export_generated_header_next_to_rust_shared_library() -> Result<(), Box<dyn Error>> {
+    let test_root = std::env::temp_dir().join(format!(
+        "shared-library-builder-cbindgen-test-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+    let source_root = test_root.join("src");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&source_root)?;
+    fs::create_dir_all(&build_root)?;
+
+    let context = LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    );
+    let library = RustLibrary::new(
+        "rust_header",
+        LibraryLocation::Path(PathLocation::new(source_root.join("rust_header"))),
+    )
+    .cbindgen(CbindgenConfig::Inline("language = \"C\"".to_string()));
+
+    let header = context.build_root().join("include").join("rust_header.h");
+    fs::create_dir_all(header.parent().unwrap())?;
+    fs::write(&header, b"int rust_header_answer(void);")?;
+
+    let compiled_library = context.build_root().join(context.profile()).join(
+        CompiledLibraryName::Default.file_name("rust_header", context.target(), false),
+    );
+    fs::create_dir_all(compiled_library.parent().unwrap())?;
+    fs::write(&compiled_library, b"rust library")?;
+
+    assert_eq!(library.header_path(&context), header);
+    assert_eq!(
+        library.native_library_include_headers(&context),
+        vec![context.build_root().join("include")]
+    );
+
+    let exported_path = library.export_compiled_library(&context)?;
+    let exported_header = exported_path.with_file_name("rust_header.h");
+
+    assert_eq!(fs::read(exported_header)?, b"int rust_header_answer(void);");
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn generate_header_with_cbindgen_after_compiling() -> Result<(), Box<dyn Error>> {
+    let test_root = std::env::temp_dir().join(format!(
+        "shared-library-builder-cbindgen-compile-test-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+
+    // cbindgen is replaced by a script that writes its configuration into the header.
+    // The test runs in its own process with the script on the PATH,
+    // instead of changing the environment of the other tests that run in parallel
+    if std::env::var_os("CBINDGEN_STUB").is_none() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let stub_directory = test_root.join("bin");
+        fs::create_dir_all(&stub_directory)?;
+        let stub = stub_directory.join("cbindgen");
+        fs::write(
+            &stub,
+            r#"#!/bin/sh
+while [ $# -gt 0 ]; do
+    case "$1" in
+        --config) config="$2"; shift 2 ;;
+        --output) output="$2"; shift 2 ;;
+        *) crate="$1"; shift ;;
+    esac
+done
+{ echo "/* $(basename "$crate") */"; if [ -n "$config" ]; then cat "$config"; fi; } > "$output"
+"#,
+        )?;
+        fs::set_permissions(&stub, fs::Permissions::from_mode(0o755))?;
+
+        let mut paths = vec![stub_directory];
+        paths.extend(std::env::split_paths(
+            &std::env::var_os("PATH").unwrap_or_default(),
+        ));
+        let output = std::process::Command::new(std::env::current_exe()?)
+            .args(["generate_header_with_cbindgen_after_compiling", "--exact"])
+            .env("PATH", std::env::join_paths(paths)?)
+            .env("CBINDGEN_STUB", "1")
+            .output()?;
+        fs::remove_dir_all(test_root)?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}", stdout);
+        assert!(stdout.contains("1 passed"), "{}", stdout);
+        return Ok(());
+    }
+
+    let source_root = test_root.join("src");
+    let crate_source = source_root.join("rust_header");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(crate_source.join("src"))?;
+    fs::create_dir_all(&build_root)?;
+    fs::write(
+        crate_source.join("Cargo.toml"),
+        r#"[package]
+name = "rust-header"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+name = "rust_header"
+crate-type = ["cdylib"]
+"#,
+    )?;
+    fs::write(
+        crate_source.join("src").join("lib.rs"),
+        r#"#[no_mangle]
+pub extern "C" fn rust_header_answer() -> i32 {
+    42
+}
+"#,
+    )?;
+    fs::write(crate_source.join("cbindgen.toml"), "language = \"C\"\n")?;
+    fs::write(crate_source.join("custom.toml"), "language = \"C++\"\n")?;
+
+    let context = LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    );
+    let library_with = |config: CbindgenConfig| {
+        RustLibrary::new(
+            "rust_header",
+            LibraryLocation::Path(PathLocation::new(&crate_source)),
+        )
+        .cbindgen(config)
+    };
+    let header = context.build_root().join("include").join("rust_header.h");
+
+    let library = library_with(CbindgenConfig::Crate);
+    library.force_compile(&context)?;
+    assert_eq!(library.header_path(&context), header);
+    assert_eq!(
+        fs::read_to_string(&header)?,
+        "/* rust_header */\nlanguage = \"C\"\n"
+    );
+    assert_eq!(library.exported_headers(&context), vec![header.clone()]);
+    assert_eq!(
+        library.native_library_include_headers(&context),
+        vec![context.build_root().join("include")]
+    );
+
+    let library = library_with(CbindgenConfig::File(PathBuf::from("custom.toml")));
+    library.force_compile(&context)?;
+    assert_eq!(
+        fs::read_to_string(&header)?,
+        "/* rust_header */\nlanguage = \"C++\"\n"
+    );
+
+    let library = library_with(CbindgenConfig::Inline("style = \"type\"".to_string()));
+    library.force_compile(&context)?;
+    assert_eq!(
+        fs::read_to_string(&header)?,
+        "/* rust_header */\nstyle = \"type\""
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
@@ -0,0 +1,110 @@
+#![cfg(unix)]
+
+use shared_library_builder::{
+    CompiledLibraryName, Library, LibraryCompilationContext, LibraryLocation, LibraryOrigin,
+    LibraryTarget, PathLocation, ScriptLibrary, ScriptStep,
+};
+use std::error::Error;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[test]
+fn run_script_steps_with_expanded_placeholders() -> Result<(), Box<dyn Error>> {
+    let test_root = std::env::temp_dir().join(format!(
+        "shared-library-builder-script-test-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+    let source_root = test_root.join("src");
+    let library_source = source_root.join("script_fake");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(library_source.join("scripts"))?;
+    fs::create_dir_all(&build_root)?;
+
+    let context = LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    );
+    let library: Box<dyn Library> = ScriptLibrary::new(
+        "script_fake",
+        LibraryLocation::Path(PathLocation::new(library_source)),
+    )
+    .step(
+        ScriptStep::new("sh")
+            .arg("-c")
+            .arg("echo \"$BUILD_TARGET {profile}\" > built_for")
+            .directory("scripts")
+            .env("BUILD_TARGET", "{target}"),
+    )
+    .step(ScriptStep::new("sh").args(vec![
+        "-c",
+        "mkdir -p {prefix}/lib && touch {prefix}/lib/libscript_fake.a",
+    ]))
+    .into();
+
+    // script libraries are serializable, including their steps
+    let serialized = serde_json::to_string(&library)?;
+    let mut library: Box<dyn Library> = serde_json::from_str(&serialized)?;
+    library.be_static();
+
+    let compiled_path = library.compile(&context)?;
+    let prefix = context.build_root().join("script_fake");
+
+    assert_eq!(compiled_path, prefix.join("lib").join("libscript_fake.a"));
+    assert_eq!(
+        fs::read_to_string(source_root.join("script_fake/scripts/built_for"))?.trim(),
+        format!("{} release", context.target())
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn recompile_shared_library_after_editing_its_sources() -> Result<(), Box<dyn Error>> {
+    let test_root = std::env::temp_dir().join(format!(
+        "shared-library-builder-script-recompile-test-{}-{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+    let library_source = test_root.join("src").join("script_edited");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&library_source)?;
+    fs::create_dir_all(&build_root)?;
+    fs::write(library_source.join("version.txt"), "1")?;
+
+    let context = LibraryCompilationContext::new(
+        test_root.join("src"),
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    );
+    let file_name =
+        CompiledLibraryName::Default.file_name("script_edited", context.target(), false);
+    let copy_version = format!(
+        "mkdir -p {{prefix}}/lib && cp {{source}}/version.txt {{prefix}}/lib/{}",
+        file_name
+    );
+    let library = ScriptLibrary::new(
+        "script_edited",
+        LibraryLocation::Path(PathLocation::new(&library_source)),
+    )
+    .step(ScriptStep::new("sh").args(vec!["-c", copy_version.as_str()]));
+
+    let exported_library = library.compile(&context)?;
+    assert_eq!(fs::read_to_string(&exported_library)?, "1");
+
+    // the recorded origin does not prevent compiling the edited sources
+    fs::write(library_source.join("version.txt"), "2")?;
+    assert_eq!(library.compile(&context)?, exported_library);
+    assert_eq!(fs::read_to_string(&exported_library)?, "2");
+    assert_eq!(
+        LibraryOrigin::read(&exported_library),
+        Some(library.compiled_origin(&context)?)
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
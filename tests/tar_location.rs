@@ -0,0 +1,838 @@
+#![cfg(all(unix, feature = "tar-location"))]
+
+use shared_library_builder::{
+    create_prebuilt_bundle, prebuilt_bundle_origin, sha256_file, DownloadCache, HeaderOnlyLibrary,
+    Library, LibraryCompilationContext, LibraryLocation, LibraryOrigin, LibraryTarget,
+    PathLocation, PrebuiltUrlTemplate, RustLibrary, ScriptLibrary, ScriptStep, TarArchive,
+    TarUrlLocation, UrlMirror,
+};
+use std::error::Error;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn test_root(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(std::env::temp_dir().join(format!(
+        "shared-library-builder-{}-test-{}-{}",
+        name,
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    )))
+}
+
+/// Serve the file to every request and return the url of the file
+fn serve(file: &Path) -> Result<String, Box<dyn Error>> {
+    Ok(serve_counting(file)?.0)
+}
+
+/// Serve the file and return its url and the amount of served requests
+fn serve_counting(file: &Path) -> Result<(String, Arc<AtomicUsize>), Box<dyn Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!(
+        "http://{}/{}",
+        listener.local_addr()?,
+        file.file_name().unwrap().to_string_lossy()
+    );
+    let body = fs::read(file)?;
+    let requests = Arc::new(AtomicUsize::new(0));
+    let served_requests = requests.clone();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            served_requests.fetch_add(1, Ordering::SeqCst);
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let mut request = vec![];
+            let mut buffer = [0u8; 1024];
+            while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => request.extend_from_slice(&buffer[..read]),
+                }
+            }
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Type: application/octet-stream\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(header.as_bytes());
+            if !request.starts_with(b"HEAD") {
+                let _ = stream.write_all(&body);
+            }
+        }
+    });
+
+    Ok((url, requests))
+}
+
+/// Create a tarball with a single folder and return its path,
+/// the compression is chosen by the extension
+fn tarball(root: &Path, name: &str, extension: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let content = root.join("content").join(name);
+    fs::create_dir_all(&content)?;
+    fs::write(
+        content.join("library.c"),
+        "int library(void) { return 42; }\n",
+    )?;
+
+    let archive = root.join(format!("{}.{}", name, extension));
+    let status = Command::new("tar")
+        .current_dir(root.join("content"))
+        .arg("caf")
+        .arg(&archive)
+        .arg(name)
+        .status()?;
+    assert!(status.success());
+    Ok(archive)
+}
+
+fn compilation_context(test_root: &Path) -> Result<LibraryCompilationContext, Box<dyn Error>> {
+    let source_root = test_root.join("src");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&source_root)?;
+    fs::create_dir_all(&build_root)?;
+
+    Ok(LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    ))
+}
+
+#[test]
+fn verify_checksum_before_unpacking() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-checksum")?;
+    let context = compilation_context(&test_root)?;
+    let archive = tarball(&test_root, "library-1.0", "tar.gz")?;
+    let sha256 = sha256_file(&archive)?;
+    let url = serve(&archive)?;
+    let default_directory = Path::new("library");
+
+    let wrong_sha256 = "0".repeat(64);
+    let location = LibraryLocation::Tar(
+        TarUrlLocation::new(&url)
+            .sources("library-1.0")
+            .sha256(&wrong_sha256),
+    );
+    let error = location
+        .ensure_sources(default_directory, &context)
+        .unwrap_err();
+    let message = format!("{:?}", error);
+    assert!(message.contains(&wrong_sha256), "{}", message);
+    assert!(message.contains(&sha256), "{}", message);
+    let sources = location.sources_directory(default_directory, &context);
+    assert!(!sources.exists());
+
+    let location = LibraryLocation::Tar(
+        TarUrlLocation::new(&url)
+            .sources("library-1.0")
+            .sha256(sha256.to_uppercase()),
+    );
+    location.ensure_sources(default_directory, &context)?;
+    assert!(sources.join("library.c").exists());
+    assert!(!sources.join("library-1.0.tar.gz").exists());
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn strict_checksums_refuse_archives_without_checksum() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-strict")?;
+    let context = compilation_context(&test_root)?.with_strict_checksums(true);
+    let default_directory = Path::new("library");
+
+    let location = LibraryLocation::Tar(TarUrlLocation::new("http://127.0.0.1:9/library.tar.gz"));
+    let error = location
+        .ensure_sources(default_directory, &context)
+        .unwrap_err();
+    assert!(format!("{:?}", error).contains("has no sha256 checksum"));
+    assert!(!location
+        .sources_directory(default_directory, &context)
+        .exists());
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn detect_archive_type_from_url() {
+    assert_eq!(
+        TarArchive::from_url("https://ftp.gnu.org/gnu/gmp/gmp-6.3.0.tar.xz"),
+        Some(TarArchive::Xz)
+    );
+    assert_eq!(
+        TarArchive::from_url("https://sourceware.org/pub/bzip2/bzip2-1.0.8.TAR.BZ2"),
+        Some(TarArchive::Bz2)
+    );
+    assert_eq!(
+        TarArchive::from_url("https://example.com/zstd-1.5.6.tar.zst?download=1"),
+        Some(TarArchive::Zst)
+    );
+    assert_eq!(
+        TarArchive::from_url("https://example.com/library.tgz"),
+        Some(TarArchive::Gz)
+    );
+    assert_eq!(
+        TarArchive::from_url("https://example.com/library.tar"),
+        Some(TarArchive::None)
+    );
+    assert_eq!(
+        TarArchive::from_url("https://example.com/download/latest"),
+        None
+    );
+}
+
+#[test]
+fn unpack_detected_archive_types() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-formats")?;
+    let context = compilation_context(&test_root)?;
+
+    for extension in ["tar.bz2", "tar.zst", "tar", "tar.xz"] {
+        let archive = tarball(&test_root, "library-1.0", extension)?;
+        assert_eq!(
+            TarArchive::from_content(&archive)?,
+            TarArchive::from_url(&archive.to_string_lossy())
+        );
+
+        // without an extension the type is detected from the content
+        let download = test_root.join(format!("download-{}", extension.replace('.', "-")));
+        fs::rename(&archive, &download)?;
+
+        let default_directory = PathBuf::from(extension);
+        let location =
+            LibraryLocation::Tar(TarUrlLocation::new(serve(&download)?).sources("library-1.0"));
+        location.ensure_sources(&default_directory, &context)?;
+        assert!(location
+            .sources_directory(&default_directory, &context)
+            .join("library.c")
+            .exists());
+    }
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn reuse_downloads_from_cache() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-cache")?;
+    let cache_directory = test_root.join("cache");
+    let context = compilation_context(&test_root)?.with_download_cache(&cache_directory);
+    let archive = tarball(&test_root, "library-1.0", "tar.gz")?;
+    let sha256 = sha256_file(&archive)?;
+    let (url, requests) = serve_counting(&archive)?;
+    let default_directory = Path::new("library");
+
+    let location = LibraryLocation::Tar(
+        TarUrlLocation::new(&url)
+            .sources("library-1.0")
+            .sha256(&sha256),
+    );
+    let sources = location.sources_directory(default_directory, &context);
+
+    location.ensure_sources(default_directory, &context)?;
+    let served_requests = requests.load(Ordering::SeqCst);
+    assert!(served_requests > 0);
+
+    fs::remove_dir_all(&sources)?;
+    location.ensure_sources(default_directory, &context)?;
+    assert!(sources.join("library.c").exists());
+    assert_eq!(requests.load(Ordering::SeqCst), served_requests);
+
+    let cache = DownloadCache::new(&cache_directory);
+    let cached = cache.list()?;
+    assert_eq!(cached.len(), 1);
+    assert_eq!(cached[0].url, url);
+    assert_eq!(sha256_file(&cached[0].path)?, sha256);
+
+    // a different checksum is a different download
+    assert!(cache.get(&url, None).is_none());
+
+    assert!(cache.prune(Duration::from_secs(3600))?.is_empty());
+    assert_eq!(cache.prune(Duration::ZERO)?.len(), 1);
+    assert!(cache.list()?.is_empty());
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn offline_build_lists_unavailable_sources() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-offline")?;
+    let cache_directory = test_root.join("cache");
+    let context = compilation_context(&test_root)?.with_download_cache(&cache_directory);
+    let archive = tarball(&test_root, "library-1.0", "tar.gz")?;
+    let (url, requests) = serve_counting(&archive)?;
+
+    let cached = LibraryLocation::Tar(TarUrlLocation::new(&url).sources("library-1.0"));
+    cached.ensure_sources(Path::new("warmup"), &context)?;
+    let served_requests = requests.load(Ordering::SeqCst);
+
+    let missing_url = "http://127.0.0.1:9/missing-1.0.tar.gz";
+    let library = HeaderOnlyLibrary::new("headers", cached.clone())
+        .with_headers("library.c")
+        .depends(
+            HeaderOnlyLibrary::new(
+                "missing",
+                LibraryLocation::Tar(TarUrlLocation::new(missing_url)),
+            )
+            .into(),
+        );
+
+    let offline_context = context.clone().with_offline(true);
+    let error = library.compile(&offline_context).unwrap_err();
+    let message = format!("{:?}", error);
+    assert!(
+        message.contains(&format!("missing: {}", missing_url)),
+        "{}",
+        message
+    );
+    assert!(!message.contains(&url), "{}", message);
+
+    // cached downloads are used without network access
+    cached.ensure_sources(Path::new("headers"), &offline_context)?;
+    assert!(test_root
+        .join("src")
+        .join("headers")
+        .join("library.c")
+        .exists());
+    assert_eq!(requests.load(Ordering::SeqCst), served_requests);
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn download_archives_from_mirrors() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-mirror")?;
+    let archive = tarball(&test_root, "library-1.0", "tar.gz")?;
+    let sha256 = sha256_file(&archive)?;
+    let served_url = serve(&archive)?;
+    let mirror = served_url.trim_end_matches("library-1.0.tar.gz");
+
+    let context = compilation_context(&test_root)?.with_mirror(
+        UrlMirror::new("https://example.com/releases/", "http://127.0.0.1:9/").fallback(mirror),
+    );
+    let default_directory = Path::new("library");
+
+    let location = LibraryLocation::Tar(
+        TarUrlLocation::new("https://example.com/releases/library-1.0.tar.gz")
+            .sources("library-1.0")
+            .sha256(&sha256),
+    );
+    location.ensure_sources(default_directory, &context)?;
+    assert!(location
+        .sources_directory(default_directory, &context)
+        .join("library.c")
+        .exists());
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+/// Create an uncompressed tarball with entries written as is, bypassing the path checks of the builder.
+/// Entries with a link are symbolic links.
+fn raw_tarball(
+    root: &Path,
+    name: &str,
+    entries: &[(&str, Option<&str>)],
+) -> Result<PathBuf, Box<dyn Error>> {
+    let archive = root.join(format!("{}.tar", name));
+    let mut builder = tar::Builder::new(fs::File::create(&archive)?);
+    for (path, link) in entries {
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+        header.set_mode(0o644);
+        let data: &[u8] = match link {
+            Some(link) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+                b""
+            }
+            None => b"evil",
+        };
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        builder.append(&header, data)?;
+    }
+    builder.finish()?;
+    Ok(archive)
+}
+
+#[test]
+fn refuse_unsafe_archive_entries() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-unsafe")?;
+    let context = compilation_context(&test_root)?;
+
+    let archives = [
+        ("parent", vec![("library-1.0/../../evil.txt", None)]),
+        ("absolute", vec![("/tmp/evil.txt", None)]),
+        (
+            "symlink",
+            vec![("library-1.0/escape", Some("../../../outside"))],
+        ),
+        ("absolute-symlink", vec![("library-1.0/etc", Some("/etc"))]),
+        (
+            "chained-symlink",
+            vec![("lib/l", Some("..")), ("escape", Some("lib/l/.."))],
+        ),
+        (
+            "pending-symlink",
+            vec![("escape", Some("later/..")), ("later", Some("."))],
+        ),
+    ];
+
+    for (name, entries) in archives {
+        let archive = raw_tarball(&test_root, name, &entries)?;
+        let default_directory = PathBuf::from(name);
+        let location = LibraryLocation::Tar(TarUrlLocation::new(serve(&archive)?));
+
+        let error = location
+            .ensure_sources(&default_directory, &context)
+            .unwrap_err();
+        let message = format!("{:?}", error);
+        assert!(message.contains("Refusing to unpack"), "{}", message);
+        assert!(!location
+            .sources_directory(&default_directory, &context)
+            .exists());
+    }
+
+    // nothing is left behind, not even the temporary directories
+    assert_eq!(fs::read_dir(test_root.join("src"))?.count(), 0);
+    assert!(!test_root.join("evil.txt").exists());
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn preserve_executable_bits_of_unpacked_files() -> Result<(), Box<dyn Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let test_root = test_root("tar-permissions")?;
+    let context = compilation_context(&test_root)?;
+    let content = test_root.join("content").join("library-1.0");
+    fs::create_dir_all(&content)?;
+    fs::write(content.join("configure"), "#!/bin/sh\nexit 0\n")?;
+    fs::set_permissions(content.join("configure"), fs::Permissions::from_mode(0o755))?;
+    let archive = tarball(&test_root, "library-1.0", "tar.xz")?;
+    let default_directory = Path::new("library");
+
+    let location =
+        LibraryLocation::Tar(TarUrlLocation::new(serve(&archive)?).sources("library-1.0"));
+    location.ensure_sources(default_directory, &context)?;
+
+    let sources = location.sources_directory(default_directory, &context);
+    let mode = fs::metadata(sources.join("configure"))?
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o111, 0o111);
+    assert!(sources.join("library.c").exists());
+
+    // a missing sources folder does not leave an empty source directory behind
+    let location = LibraryLocation::Tar(TarUrlLocation::new(serve(&archive)?).sources("missing"));
+    let missing_directory = Path::new("missing");
+    assert!(location
+        .ensure_sources(missing_directory, &context)
+        .is_err());
+    assert!(!location
+        .sources_directory(missing_directory, &context)
+        .exists());
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn strip_leading_directories_of_entries() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-strip")?;
+    let context = compilation_context(&test_root)?;
+    let url = serve(&tarball(&test_root, "library-1.0", "tar.gz")?)?;
+
+    let locations = [
+        ("auto", TarUrlLocation::new(&url).auto_strip_components()),
+        ("count", TarUrlLocation::new(&url).strip_components(1)),
+    ];
+    for (name, location) in locations {
+        let location = LibraryLocation::Tar(location);
+        location.ensure_sources(Path::new(name), &context)?;
+        let sources = location.sources_directory(Path::new(name), &context);
+        assert!(sources.join("library.c").exists(), "{}", name);
+    }
+
+    let location = LibraryLocation::Tar(TarUrlLocation::new(&url).strip_components(2));
+    let error = location
+        .ensure_sources(Path::new("too-many"), &context)
+        .unwrap_err();
+    assert!(format!("{:?}", error).contains("no entries"));
+
+    // several top-level directories are kept as they are
+    let archive = raw_tarball(
+        &test_root,
+        "several",
+        &[("include/library.h", None), ("src/library.c", None)],
+    )?;
+    let location =
+        LibraryLocation::Tar(TarUrlLocation::new(serve(&archive)?).auto_strip_components());
+    location.ensure_sources(Path::new("several"), &context)?;
+    let sources = location.sources_directory(Path::new("several"), &context);
+    assert!(sources.join("include").join("library.h").exists());
+    assert!(sources.join("src").join("library.c").exists());
+
+    // links are checked against the stripped paths
+    let archive = raw_tarball(
+        &test_root,
+        "stripped-link",
+        &[
+            ("library-1.0/library.c", None),
+            ("library-1.0/escape", Some("../outside")),
+        ],
+    )?;
+    let location =
+        LibraryLocation::Tar(TarUrlLocation::new(serve(&archive)?).auto_strip_components());
+    let error = location
+        .ensure_sources(Path::new("stripped-link"), &context)
+        .unwrap_err();
+    assert!(format!("{:?}", error).contains("Refusing to unpack"));
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn retrieve_prebuilt_library_from_url_template() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-prebuilt")?;
+    let context = compilation_context(&test_root)?;
+    let prebuilt = test_root.join("prebuilt");
+    fs::write(&prebuilt, "prebuilt library")?;
+    let sha256 = sha256_file(&prebuilt)?;
+    let url = serve(&prebuilt)?;
+    let template = format!(
+        "{}{{name}}/{{version}}/{{asset}}",
+        url.trim_end_matches("prebuilt")
+    );
+
+    let library_with = |prebuilt_url: PrebuiltUrlTemplate| {
+        ScriptLibrary::new(
+            "vendored",
+            LibraryLocation::Tar(
+                TarUrlLocation::new("http://127.0.0.1:9/vendored-1.0.tar.gz")
+                    .prebuilt_url(prebuilt_url),
+            ),
+        )
+    };
+    let asset_name =
+        library_with(PrebuiltUrlTemplate::new(&template)).prebuilt_library_asset_name(&context);
+
+    let prebuilt_url = PrebuiltUrlTemplate::new(&template).version("1.0");
+    assert_eq!(
+        prebuilt_url.url(&library_with(prebuilt_url.clone()), None, &context),
+        Some(format!(
+            "{}vendored/1.0/{}",
+            url.trim_end_matches("prebuilt"),
+            asset_name
+        ))
+    );
+    // the version is required when the template refers to it
+    let unversioned = PrebuiltUrlTemplate::new(&template);
+    assert!(unversioned
+        .url(&library_with(unversioned.clone()), None, &context)
+        .is_none());
+
+    // a mismatching checksum falls back to compiling from sources
+    let library = library_with(prebuilt_url.clone().sha256(&asset_name, "0".repeat(64)));
+    assert!(library.retrieve_prebuilt_library(&context).is_none());
+    assert!(!library.exported_library_path(&context).exists());
+
+    // without a known checksum the companion .sha256 is verified, here the server
+    // answers with the library itself, which is not a valid checksum
+    let strict_context = context.clone().with_strict_checksums(true);
+    assert!(library_with(prebuilt_url.clone())
+        .retrieve_prebuilt_library(&strict_context)
+        .is_none());
+    assert!(!library.exported_library_path(&context).exists());
+
+    let library = library_with(prebuilt_url.sha256(&asset_name, &sha256));
+    let retrieved = library.retrieve_prebuilt_library(&context).unwrap();
+    assert_eq!(retrieved, library.exported_library_path(&context));
+    assert_eq!(fs::read_to_string(&retrieved)?, "prebuilt library");
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn retrieve_prebuilt_bundles_of_static_libraries() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-bundle")?;
+
+    // compile a static library and bundle its prefix
+    let publisher = compilation_context(&test_root.join("publisher"))?;
+    let sources = test_root.join("bundled");
+    fs::create_dir_all(&sources)?;
+    let mut library = ScriptLibrary::new(
+        "bundled",
+        LibraryLocation::Path(PathLocation::new(&sources)),
+    )
+    .step(ScriptStep::new("sh").args(vec![
+        "-c",
+        "mkdir -p {prefix}/lib/pkgconfig {prefix}/include {prefix}/build \\
+            && touch {prefix}/lib/libbundled.a {prefix}/include/bundled.h {prefix}/build/object.o \\
+            && printf 'prefix={prefix}\\nlibdir=${prefix}/lib\\n' > {prefix}/lib/pkgconfig/bundled.pc",
+    ]));
+    library.be_static();
+    library.compile(&publisher)?;
+
+    let bundle = create_prebuilt_bundle(&library, &publisher)?;
+    assert_eq!(
+        bundle.file_name().unwrap().to_string_lossy(),
+        library.prebuilt_bundle_asset_name(&publisher)
+    );
+
+    // a transitive dependency is bundled separately
+    let transitive_sources = test_root.join("transitive");
+    fs::create_dir_all(&transitive_sources)?;
+    let transitive_step = "mkdir -p {prefix}/lib && touch {prefix}/lib/libtransitive.a";
+    let mut transitive = ScriptLibrary::new(
+        "transitive",
+        LibraryLocation::Path(PathLocation::new(&transitive_sources)),
+    )
+    .step(ScriptStep::new("sh").args(vec!["-c", transitive_step]));
+    transitive.be_static();
+    transitive.compile(&publisher)?;
+    let transitive_bundle = create_prebuilt_bundle(&transitive, &publisher)?;
+
+    // a dependency bundled together with its dependencies is neither fetched nor compiled
+    let context = compilation_context(&test_root.join("consumer"))?;
+    let prefix = context.build_root().join("bundled");
+    let transitive = ScriptLibrary::new(
+        "transitive",
+        LibraryLocation::Path(
+            PathLocation::new(test_root.join("missing"))
+                .prebuilt_library_directory(publisher.build_root()),
+        ),
+    )
+    .step(ScriptStep::new("sh").args(vec!["-c", "exit 1"]));
+    let dependency = ScriptLibrary::new(
+        "bundled",
+        LibraryLocation::Path(
+            PathLocation::new(test_root.join("missing"))
+                .prebuilt_library_directory(publisher.build_root()),
+        ),
+    )
+    .depends(Box::new(transitive))
+    .step(ScriptStep::new("sh").args(vec!["-c", "exit 1"]));
+    let dependent_step = format!(
+        "test -f {}/lib/libbundled.a && mkdir -p {{prefix}}/lib && touch {{prefix}}/lib/libdependent.a",
+        prefix.display()
+    );
+    let mut dependent = ScriptLibrary::new(
+        "dependent",
+        LibraryLocation::Path(PathLocation::new(&sources)),
+    )
+    .depends(Box::new(dependency))
+    .step(ScriptStep::new("sh").args(vec!["-c", &dependent_step]));
+    dependent.be_static();
+    dependent.compile(&context)?;
+
+    assert!(prefix.join("include").join("bundled.h").exists());
+    // only libraries, headers and pkg-config files are bundled
+    assert!(!prefix.join("build").exists());
+    assert!(prebuilt_bundle_origin(&prefix).is_some());
+    assert!(
+        fs::read_to_string(prefix.join("lib").join("pkgconfig").join("bundled.pc"))?
+            .starts_with(&format!("prefix={}\n", prefix.display()))
+    );
+    assert!(context
+        .build_root()
+        .join("transitive")
+        .join("lib")
+        .join("libtransitive.a")
+        .exists());
+
+    // without a bundle of its dependency the bundled library is compiled from sources too
+    fs::remove_file(transitive_bundle)?;
+    let context = compilation_context(&test_root.join("partial"))?;
+    let prefix = context.build_root().join("bundled");
+    let transitive = ScriptLibrary::new(
+        "transitive",
+        LibraryLocation::Path(
+            PathLocation::new(&transitive_sources)
+                .prebuilt_library_directory(publisher.build_root()),
+        ),
+    )
+    .step(ScriptStep::new("sh").args(vec!["-c", transitive_step]));
+    let dependency = ScriptLibrary::new(
+        "bundled",
+        LibraryLocation::Path(
+            PathLocation::new(&sources).prebuilt_library_directory(publisher.build_root()),
+        ),
+    )
+    .depends(Box::new(transitive))
+    .step(ScriptStep::new("sh").args(vec![
+        "-c",
+        "mkdir -p {prefix}/lib && touch {prefix}/lib/libbundled.a",
+    ]));
+    let dependent_step = format!(
+        "test -f {}/lib/libbundled.a && mkdir -p {{prefix}}/lib && touch {{prefix}}/lib/libdependent.a",
+        prefix.display()
+    );
+    let mut dependent = ScriptLibrary::new(
+        "dependent",
+        LibraryLocation::Path(PathLocation::new(&sources)),
+    )
+    .depends(Box::new(dependency))
+    .step(ScriptStep::new("sh").args(vec!["-c", &dependent_step]));
+    dependent.be_static();
+    dependent.compile(&context)?;
+
+    assert!(prebuilt_bundle_origin(&prefix).is_none());
+    assert!(!prefix.join("include").join("bundled.h").exists());
+    assert!(context
+        .build_root()
+        .join("transitive")
+        .join("lib")
+        .join("libtransitive.a")
+        .exists());
+
+    // a static library is retrieved from a url template instead of compiling it
+    let context = compilation_context(&test_root.join("downloader"))?;
+    let url = serve(&bundle)?;
+    let mut library = ScriptLibrary::new(
+        "bundled",
+        LibraryLocation::Tar(
+            TarUrlLocation::new("http://127.0.0.1:9/bundled-1.0.tar.gz").prebuilt_url(
+                PrebuiltUrlTemplate::new(
+                    url.replace(bundle.file_name().unwrap().to_str().unwrap(), "{asset}"),
+                )
+                .sha256(
+                    library.prebuilt_bundle_asset_name(&context),
+                    sha256_file(&bundle)?,
+                ),
+            ),
+        ),
+    )
+    .step(ScriptStep::new("sh").args(vec!["-c", "exit 1"]));
+    library.be_static();
+    assert_eq!(
+        library.compile(&context)?,
+        context
+            .build_root()
+            .join("bundled")
+            .join("lib")
+            .join("libbundled.a")
+    );
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn refuse_bundles_of_libraries_installed_into_the_build_root() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-bundle-build-root")?;
+    let context = compilation_context(&test_root)?;
+    let prebuilt = test_root.join("prebuilt");
+    fs::create_dir_all(&prebuilt)?;
+    let kept = context.build_root().join("lib").join("libother.a");
+    fs::create_dir_all(kept.parent().unwrap())?;
+    fs::write(&kept, "other library")?;
+
+    // rust libraries without cargo-c are installed into the build root itself
+    let library = RustLibrary::new(
+        "rust_bundled",
+        LibraryLocation::Path(
+            PathLocation::new(test_root.join("missing")).prebuilt_library_directory(&prebuilt),
+        ),
+    );
+    let bundle = tarball(&test_root, "rust_bundled", "tar.gz")?;
+    fs::rename(
+        bundle,
+        prebuilt.join(library.prebuilt_bundle_asset_name(&context)),
+    )?;
+
+    assert!(create_prebuilt_bundle(&library, &context).is_err());
+    assert!(library.retrieve_prebuilt_bundle(&context).is_none());
+    assert_eq!(fs::read_to_string(&kept)?, "other library");
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
+
+#[test]
+fn refresh_prebuilt_libraries_of_another_origin() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("tar-prebuilt-origin")?;
+    let context = compilation_context(&test_root)?;
+    let prebuilt = test_root.join("prebuilt");
+    fs::write(&prebuilt, "prebuilt library")?;
+    let (url, requests) = serve_counting(&prebuilt)?;
+    let template = format!(
+        "{}{{name}}/{{version}}/{{asset}}",
+        url.trim_end_matches("prebuilt")
+    );
+
+    let library_with =
+        |location: TarUrlLocation| ScriptLibrary::new("versioned", LibraryLocation::Tar(location));
+    let archive_url = "http://127.0.0.1:9/versioned-1.0.tar.gz";
+    let asset_name =
+        library_with(TarUrlLocation::new(archive_url)).prebuilt_library_asset_name(&context);
+    let sha256 = sha256_file(&prebuilt)?;
+
+    let library_of = |version: &str| {
+        library_with(
+            TarUrlLocation::new(archive_url).prebuilt_url(
+                PrebuiltUrlTemplate::new(&template)
+                    .version(version)
+                    .sha256(&asset_name, &sha256),
+            ),
+        )
+    };
+    let origin_of = |version: &str| {
+        LibraryOrigin::prebuilt(
+            format!(
+                "{}versioned/{}/{}",
+                url.trim_end_matches("prebuilt"),
+                version,
+                asset_name
+            ),
+            &asset_name,
+        )
+    };
+
+    // the origin of a retrieved library is recorded next to it
+    let exported = library_of("1.0")
+        .retrieve_prebuilt_library(&context)
+        .unwrap();
+    assert!(LibraryOrigin::path(&exported).exists());
+    assert_eq!(LibraryOrigin::read(&exported), Some(origin_of("1.0")));
+
+    // a library of the same origin is reused
+    let served_requests = requests.load(Ordering::SeqCst);
+    library_of("1.0")
+        .retrieve_prebuilt_library(&context)
+        .unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), served_requests);
+
+    // a library of another version is retrieved again
+    library_of("2.0")
+        .retrieve_prebuilt_library(&context)
+        .unwrap();
+    assert!(requests.load(Ordering::SeqCst) > served_requests);
+    assert_eq!(LibraryOrigin::read(&exported), Some(origin_of("2.0")));
+
+    // so is a library that was modified after it was retrieved
+    fs::write(&exported, "modified")?;
+    assert_eq!(LibraryOrigin::read(&exported), None);
+    library_of("2.0")
+        .retrieve_prebuilt_library(&context)
+        .unwrap();
+    assert_eq!(fs::read_to_string(&exported)?, "prebuilt library");
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}
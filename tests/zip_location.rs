@@ -0,0 +1,150 @@
+#![cfg(all(unix, feature = "zip-location"))]
+
+use shared_library_builder::{
+    LibraryCompilationContext, LibraryLocation, LibraryTarget, ZipUrlLocation,
+};
+use std::error::Error;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::SimpleFileOptions;
+
+fn test_root(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(std::env::temp_dir().join(format!(
+        "shared-library-builder-{}-test-{}-{}",
+        name,
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    )))
+}
+
+/// Serve the file to every request and return the url of the file
+fn serve(file: &Path) -> Result<String, Box<dyn Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!(
+        "http://{}/{}",
+        listener.local_addr()?,
+        file.file_name().unwrap().to_string_lossy()
+    );
+    let body = fs::read(file)?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let mut request = vec![];
+            let mut buffer = [0u8; 1024];
+            while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => request.extend_from_slice(&buffer[..read]),
+                }
+            }
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Type: application/zip\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(header.as_bytes());
+            if !request.starts_with(b"HEAD") {
+                let _ = stream.write_all(&body);
+            }
+        }
+    });
+
+    Ok(url)
+}
+
+/// Create a zip archive with the given files, entries with a link are symbolic links
+fn zip_archive(
+    root: &Path,
+    name: &str,
+    entries: &[(&str, u32, Option<&str>)],
+) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(root)?;
+    let archive = root.join(format!("{}.zip", name));
+    let mut writer = zip::ZipWriter::new(fs::File::create(&archive)?);
+    for (path, mode, link) in entries {
+        let options = SimpleFileOptions::default().unix_permissions(*mode);
+        match link {
+            Some(link) => writer.add_symlink(*path, *link, options)?,
+            None => {
+                writer.start_file(*path, options)?;
+                writer.write_all(b"#!/bin/sh\nexit 0\n")?;
+            }
+        }
+    }
+    writer.finish()?;
+    Ok(archive)
+}
+
+fn compilation_context(test_root: &Path) -> Result<LibraryCompilationContext, Box<dyn Error>> {
+    let source_root = test_root.join("src");
+    let build_root = test_root.join("build");
+    fs::create_dir_all(&source_root)?;
+    fs::create_dir_all(&build_root)?;
+
+    Ok(LibraryCompilationContext::new(
+        &source_root,
+        &build_root,
+        LibraryTarget::for_current_platform(),
+        false,
+    ))
+}
+
+#[test]
+fn extract_zip_archives_safely() -> Result<(), Box<dyn Error>> {
+    let test_root = test_root("zip-extract")?;
+    let context = compilation_context(&test_root)?;
+
+    let archive = zip_archive(
+        &test_root,
+        "library-1.0",
+        &[
+            ("library-1.0/configure", 0o755, None),
+            ("library-1.0/src/library.c", 0o644, None),
+            ("library-1.0/include", 0o777, Some("src")),
+        ],
+    )?;
+    let default_directory = Path::new("library");
+    let location =
+        LibraryLocation::Zip(ZipUrlLocation::new(serve(&archive)?).sources("library-1.0"));
+    location.ensure_sources(default_directory, &context)?;
+
+    let sources = location.sources_directory(default_directory, &context);
+    let mode = fs::metadata(sources.join("configure"))?
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o111, 0o111);
+    assert!(sources.join("include").join("library.c").exists());
+
+    let unsafe_archives = [
+        ("parent", ("library-1.0/../../evil.txt", 0o644, None)),
+        ("absolute", ("/tmp/evil.txt", 0o644, None)),
+        (
+            "symlink",
+            ("library-1.0/escape", 0o777, Some("../../outside")),
+        ),
+    ];
+    for (name, entry) in unsafe_archives {
+        let archive = zip_archive(&test_root.join(name), name, &[entry])?;
+        let default_directory = PathBuf::from(name);
+        let location = LibraryLocation::Zip(ZipUrlLocation::new(serve(&archive)?));
+
+        let error = location
+            .ensure_sources(&default_directory, &context)
+            .unwrap_err();
+        let message = format!("{:?}", error);
+        assert!(message.contains("Refusing to unpack"), "{}", message);
+        assert!(!location
+            .sources_directory(&default_directory, &context)
+            .exists());
+    }
+
+    fs::remove_dir_all(test_root)?;
+    Ok(())
+}